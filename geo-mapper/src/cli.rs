@@ -4,6 +4,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use leader_geo_map::DEFAULT_KEY_PREFIX_SIZE;
+
 const DEFAULT_DB_REL_PATH: &str = "GeoLite2-City_20260210/GeoLite2-City.mmdb";
 const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
 
@@ -12,6 +14,14 @@ pub struct Cli {
     pub rpc_url: String,
     pub output: PathBuf,
     pub db_path: PathBuf,
+    /// Store each leader's ISO country in the map, not just its bucket.
+    pub with_country: bool,
+    /// Store each leader's quantized latitude/longitude in the map.
+    pub with_coordinates: bool,
+    /// Keep only this many bytes of each pubkey (compact v3 map).
+    pub key_prefix_bytes: Option<usize>,
+    /// Rules file overriding the rules embedded in `geo-rules`.
+    pub rules_path: Option<PathBuf>,
 }
 
 impl Cli {
@@ -21,6 +31,10 @@ impl Cli {
         let mut rpc_url: Option<String> = None;
         let mut output: Option<PathBuf> = None;
         let mut db_path = detect_default_db_path();
+        let mut with_country = false;
+        let mut with_coordinates = false;
+        let mut key_prefix_bytes: Option<usize> = None;
+        let mut rules_path: Option<PathBuf> = None;
 
         while let Some(arg) = args.next() {
             match arg.as_str() {
@@ -54,6 +68,34 @@ impl Cli {
                     };
                     db_path = PathBuf::from(value);
                 }
+                "--rules" => {
+                    let Some(value) = args.next() else {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            "missing value for --rules",
+                        )
+                        .into());
+                    };
+                    rules_path = Some(PathBuf::from(value));
+                }
+                "--with-country" => with_country = true,
+                "--with-coordinates" => with_coordinates = true,
+                "--compact" => key_prefix_bytes = Some(DEFAULT_KEY_PREFIX_SIZE),
+                "--key-prefix-bytes" => {
+                    let Some(value) = args.next() else {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            "missing value for --key-prefix-bytes",
+                        )
+                        .into());
+                    };
+                    key_prefix_bytes = Some(value.parse().map_err(|_| {
+                        io::Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("invalid value for --key-prefix-bytes: {value}"),
+                        )
+                    })?);
+                }
                 "-h" | "--help" => {
                     print_usage();
                     std::process::exit(0);
@@ -76,13 +118,17 @@ impl Cli {
             rpc_url: rpc_url.unwrap_or_else(|| DEFAULT_RPC_URL.to_string()),
             output,
             db_path,
+            with_country,
+            with_coordinates,
+            key_prefix_bytes,
+            rules_path,
         })
     }
 }
 
 fn print_usage() {
     println!(
-        "Usage: geo-mapper --output <leader_geo_map.bin> [--rpc-url <solana_rpc_url>] [--db <GeoLite2-City.mmdb>]"
+        "Usage: geo-mapper --output <leader_geo_map.bin> [--rpc-url <solana_rpc_url>] [--db <GeoLite2-City.mmdb>] [--rules <rules.json>] [--with-country] [--with-coordinates] [--compact | --key-prefix-bytes <1-32>]"
     );
 }
 
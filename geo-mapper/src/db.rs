@@ -1,39 +1,112 @@
 use std::{collections::BTreeMap, error::Error, fs, net::IpAddr, path::Path};
 
-use geo_rules::bucket_from_country_iso;
+use geo_rules::{Coordinates, RuleSet};
+use leader_geo_map::LeaderGeoMapBuilder;
 use maxminddb::{MaxMindDbError, Reader, geoip2};
 
 pub use geo_rules::GeoBucket;
 
-pub const RECORD_SIZE: usize = 33;
-
 pub type DbReader = Reader<Vec<u8>>;
 
-pub fn compute_geolocation(reader: &DbReader, ip: IpAddr) -> Result<GeoBucket, Box<dyn Error>> {
-    let result = reader.lookup(ip)?;
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geolocation {
+    pub bucket: GeoBucket,
+    /// Upper-case ISO-3166 alpha-2 code, when the database resolved one.
+    pub country: Option<String>,
+    /// City-level location, when the database resolved one.
+    pub coordinates: Option<Coordinates>,
+}
 
-    if let Some(city) = result.decode::<geoip2::City>()?
-        && let Some(iso_code) = city.country.iso_code
-    {
-        return Ok(bucket_from_country_iso(iso_code));
+impl Geolocation {
+    pub const UNKNOWN: Self = Self {
+        bucket: GeoBucket::Unknown,
+        country: None,
+        coordinates: None,
+    };
+
+    /// Whether `other` carries information this one lacks.
+    pub fn is_improved_by(&self, other: &Self) -> bool {
+        (self.bucket == GeoBucket::Unknown && other.bucket != GeoBucket::Unknown)
+            || (self.country.is_none() && other.country.is_some())
+            || (self.coordinates.is_none() && other.coordinates.is_some())
     }
+}
+
+/// How to encode the map: optional per-leader fields to store besides the
+/// bucket and key truncation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapOptions {
+    pub country: bool,
+    pub coordinates: bool,
+    pub key_prefix_bytes: Option<usize>,
+}
+
+pub fn compute_geolocation(
+    reader: &DbReader,
+    rules: &RuleSet,
+    ip: IpAddr,
+) -> Result<Geolocation, Box<dyn Error>> {
+    let result = reader.lookup(ip)?;
+
+    let Some(city) = result.decode::<geoip2::City>()? else {
+        return Ok(Geolocation::UNKNOWN);
+    };
+
+    let coordinates = match (city.location.latitude, city.location.longitude) {
+        (Some(latitude), Some(longitude)) => {
+            Some(Coordinates::new(latitude, longitude)).filter(|location| location.is_valid())
+        }
+        _ => None,
+    };
 
-    Ok(GeoBucket::Unknown)
+    Ok(Geolocation {
+        bucket: city
+            .country
+            .iso_code
+            .map_or(GeoBucket::Unknown, |iso_code| {
+                rules.bucket_for_country(iso_code)
+            }),
+        country: city.country.iso_code.map(str::to_ascii_uppercase),
+        coordinates,
+    })
 }
 
 pub fn get_db_reader(path: &Path) -> Result<DbReader, MaxMindDbError> {
     Reader::open_readfile(path)
 }
 
+/// Rules from `path`, or the rules embedded in `geo-rules`.
+pub fn load_rules(path: Option<&Path>) -> Result<RuleSet, Box<dyn Error>> {
+    let Some(path) = path else {
+        return Ok(RuleSet::embedded().clone());
+    };
+    let json = fs::read_to_string(path)?;
+    RuleSet::from_json(&json).map_err(|err| format!("{}: {err}", path.display()).into())
+}
+
+/// Writes the v2 map. With `options.country`, records also carry the leader's
+/// ISO country so the procedure can re-bucket it at runtime; with
+/// `options.coordinates`, its location so the procedure can pick the nearest
+/// region by distance. With `options.key_prefix_bytes`, writes the compact v3
+/// format that keeps only that many bytes of each pubkey.
 pub fn write_binary_map(
     path: &Path,
-    map: &BTreeMap<[u8; 32], GeoBucket>,
+    map: &BTreeMap<[u8; 32], Geolocation>,
+    options: MapOptions,
 ) -> Result<(), Box<dyn Error>> {
-    let mut output = Vec::with_capacity(map.len() * RECORD_SIZE);
-    for (pubkey, bucket) in map {
-        output.extend_from_slice(pubkey);
-        output.push(bucket.as_u8());
+    let mut builder = LeaderGeoMapBuilder::new();
+    for (pubkey, geolocation) in map {
+        builder.insert_with_location(
+            *pubkey,
+            geolocation.bucket,
+            geolocation.country.as_deref().filter(|_| options.country),
+            geolocation.coordinates.filter(|_| options.coordinates),
+        )?;
     }
+    let output = match options.key_prefix_bytes {
+        Some(key_size) => builder.build_compact(key_size)?,
+        None => builder.build()?,
+    };
 
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
@@ -46,6 +119,7 @@ pub fn write_binary_map(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use geo_rules::bucket_from_country_iso;
 
     #[test]
     fn country_to_bucket_maps_known_codes() {
@@ -53,6 +127,51 @@ mod tests {
         assert_eq!(bucket_from_country_iso("US"), GeoBucket::Na);
         assert_eq!(bucket_from_country_iso("JP"), GeoBucket::Apac);
         assert_eq!(bucket_from_country_iso("AE"), GeoBucket::Me);
-        assert_eq!(bucket_from_country_iso("BR"), GeoBucket::Unknown);
+        assert_eq!(bucket_from_country_iso("BR"), GeoBucket::Latam);
+        assert_eq!(bucket_from_country_iso("AQ"), GeoBucket::Unknown);
+    }
+
+    #[test]
+    fn load_rules_reads_file_or_falls_back_to_embedded() {
+        assert_eq!(&load_rules(None).unwrap(), RuleSet::embedded());
+
+        let path =
+            std::env::temp_dir().join(format!("geo-mapper-rules-{}.json", std::process::id()));
+        let rules = geo_rules::EMBEDDED_RULES_JSON
+            .replace(r#""countries": {}"#, r#""countries": {"BR": "NA"}"#);
+        fs::write(&path, rules).unwrap();
+        let loaded = load_rules(Some(&path));
+        fs::write(&path, "{}").unwrap();
+        let invalid = load_rules(Some(&path));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().bucket_for_country("BR"), GeoBucket::Na);
+        let err = invalid.unwrap_err().to_string();
+        assert!(err.contains(&*path.to_string_lossy()), "{err}");
+    }
+
+    #[test]
+    fn geolocation_prefers_known_bucket_and_country() {
+        let brazil = Geolocation {
+            bucket: GeoBucket::Unknown,
+            country: Some("BR".to_string()),
+            coordinates: None,
+        };
+        let germany = Geolocation {
+            bucket: GeoBucket::Eu,
+            country: Some("DE".to_string()),
+            coordinates: None,
+        };
+        let berlin = Geolocation {
+            coordinates: Some(Coordinates::new(52.52, 13.40)),
+            ..germany.clone()
+        };
+
+        assert!(Geolocation::UNKNOWN.is_improved_by(&brazil));
+        assert!(brazil.is_improved_by(&germany));
+        assert!(germany.is_improved_by(&berlin));
+        assert!(!germany.is_improved_by(&brazil));
+        assert!(!berlin.is_improved_by(&germany));
+        assert!(!germany.is_improved_by(&Geolocation::UNKNOWN));
     }
 }
@@ -3,14 +3,16 @@ mod db;
 mod metadata;
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, btree_map::Entry},
     error::Error,
     io::{self, ErrorKind},
     net::{IpAddr, SocketAddr},
 };
 
 use crate::cli::Cli;
-use crate::db::{GeoBucket, compute_geolocation, get_db_reader, write_binary_map};
+use crate::db::{
+    Geolocation, MapOptions, compute_geolocation, get_db_reader, load_rules, write_binary_map,
+};
 use crate::metadata::write_map_metadata;
 use serde_json::{Value, json};
 
@@ -29,6 +31,7 @@ fn main() {
 
 fn run() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse()?;
+    let rules = load_rules(cli.rules_path.as_deref())?;
 
     let rows = fetch_rows_from_rpc(&cli.rpc_url)?;
     println!(
@@ -43,21 +46,29 @@ fn run() -> Result<(), Box<dyn Error>> {
 
     let reader = get_db_reader(&cli.db_path)?;
 
-    let mut map: BTreeMap<[u8; 32], GeoBucket> = BTreeMap::new();
+    let mut map: BTreeMap<[u8; 32], Geolocation> = BTreeMap::new();
 
     for row in rows {
-        let bucket = compute_geolocation(&reader, row.ip)?;
+        let geolocation = compute_geolocation(&reader, &rules, row.ip)?;
 
-        map.entry(row.pubkey)
-            .and_modify(|existing| {
-                if *existing == GeoBucket::Unknown && bucket != GeoBucket::Unknown {
-                    *existing = bucket;
+        match map.entry(row.pubkey) {
+            Entry::Occupied(mut existing) => {
+                if existing.get().is_improved_by(&geolocation) {
+                    existing.insert(geolocation);
                 }
-            })
-            .or_insert(bucket);
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(geolocation);
+            }
+        }
     }
 
-    write_binary_map(&cli.output, &map)?;
+    let options = MapOptions {
+        country: cli.with_country,
+        coordinates: cli.with_coordinates,
+        key_prefix_bytes: cli.key_prefix_bytes,
+    };
+    write_binary_map(&cli.output, &map, options)?;
     let metadata = write_map_metadata(&cli.rpc_url, &cli.db_path, &cli.output, &map)?;
     let stats = metadata.stats;
 
@@ -7,7 +7,8 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::db::{GeoBucket, RECORD_SIZE};
+use crate::db::{GeoBucket, Geolocation};
+use leader_geo_map::LeaderGeoMap;
 use serde_json::{Value, json};
 use sha2::{Digest, Sha256};
 
@@ -30,29 +31,37 @@ pub fn write_map_metadata(
     rpc_url: &str,
     db_path: &Path,
     map_path: &Path,
-    map: &BTreeMap<[u8; 32], GeoBucket>,
+    map: &BTreeMap<[u8; 32], Geolocation>,
 ) -> Result<MetadataOutput, Box<dyn Error>> {
     let generated_at_unix_secs = current_unix_secs()?;
     let rpc_slot = fetch_current_slot_from_rpc(rpc_url)?;
-    let stats = compute_generation_stats(map);
+    let map_bytes = fs::read(map_path)?;
+    let encoded = LeaderGeoMap::parse(&map_bytes)?;
+    let (schema_version, record_size, key_size) =
+        (encoded.version(), encoded.record_size(), encoded.key_size());
+    let output_bytes = map_bytes.len();
+    let stats = compute_generation_stats(map, output_bytes);
     let metadata_path = metadata_path_for_map(map_path);
     let map_sha256 = sha256_file_hex(map_path)?;
     let mmdb_sha256 = sha256_file_hex(db_path)?;
+    let bucket_counts = compute_bucket_counts(map);
 
     write_metadata_file(
         &metadata_path,
         &json!({
-            "schema_version": 1,
+            "schema_version": schema_version,
             "generated_at_unix_secs": generated_at_unix_secs,
             "rpc_url": rpc_url,
             "rpc_slot": rpc_slot,
             "db_path": db_path.display().to_string(),
             "mmdb_sha256": mmdb_sha256,
-            "record_size_bytes": RECORD_SIZE,
+            "record_size_bytes": record_size,
+            "key_size_bytes": key_size,
             "total_leaders": stats.total_leaders,
             "mapped_leaders": stats.mapped_leaders,
             "unknown_leaders": stats.unknown_leaders,
             "unknown_rate_pct": stats.unknown_rate_pct,
+            "bucket_counts": bucket_counts,
             "map_size_bytes": stats.output_bytes,
             "map_sha256": map_sha256
         }),
@@ -68,11 +77,14 @@ fn current_unix_secs() -> Result<u64, Box<dyn Error>> {
     Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
 }
 
-fn compute_generation_stats(map: &BTreeMap<[u8; 32], GeoBucket>) -> GenerationStats {
+fn compute_generation_stats(
+    map: &BTreeMap<[u8; 32], Geolocation>,
+    output_bytes: usize,
+) -> GenerationStats {
     let total_leaders = map.len();
     let unknown_leaders = map
         .values()
-        .filter(|bucket| **bucket == GeoBucket::Unknown)
+        .filter(|geolocation| geolocation.bucket == GeoBucket::Unknown)
         .count();
     let mapped_leaders = total_leaders.saturating_sub(unknown_leaders);
     let unknown_rate_pct = if total_leaders == 0 {
@@ -80,7 +92,6 @@ fn compute_generation_stats(map: &BTreeMap<[u8; 32], GeoBucket>) -> GenerationSt
     } else {
         (unknown_leaders as f64 / total_leaders as f64) * 100.0
     };
-    let output_bytes = total_leaders * RECORD_SIZE;
 
     GenerationStats {
         total_leaders,
@@ -91,6 +102,18 @@ fn compute_generation_stats(map: &BTreeMap<[u8; 32], GeoBucket>) -> GenerationSt
     }
 }
 
+/// Leaders per geo bucket label, mapped leaders only. The procedure uses this
+/// distribution to weight the hash fallback for unknown leaders.
+fn compute_bucket_counts(map: &BTreeMap<[u8; 32], Geolocation>) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for geolocation in map.values() {
+        if geolocation.bucket != GeoBucket::Unknown {
+            *counts.entry(geolocation.bucket.label()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
 fn metadata_path_for_map(map_path: &Path) -> PathBuf {
     map_path.with_extension("meta.json")
 }
@@ -170,6 +193,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bucket_counts_cover_mapped_leaders_only() {
+        let map: BTreeMap<_, _> = [
+            ([1u8; 32], GeoBucket::Eu),
+            ([2u8; 32], GeoBucket::Eu),
+            ([3u8; 32], GeoBucket::Me),
+            ([4u8; 32], GeoBucket::Unknown),
+        ]
+        .into_iter()
+        .map(|(pubkey, bucket)| {
+            (
+                pubkey,
+                Geolocation {
+                    bucket,
+                    country: None,
+                    coordinates: None,
+                },
+            )
+        })
+        .collect();
+
+        assert_eq!(
+            compute_bucket_counts(&map),
+            BTreeMap::from([("EU", 2), ("ME", 1)])
+        );
+    }
+
     #[test]
     fn parse_get_slot_response_parses_result() {
         let body = r#"{"jsonrpc":"2.0","id":1,"result":400403440}"#;
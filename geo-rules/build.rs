@@ -0,0 +1,67 @@
+//! Generates the country -> bucket table (`COUNTRY_BUCKETS`) from
+//! `data/country_buckets.csv`, failing the build on a malformed or duplicate
+//! row.
+
+use std::{collections::BTreeMap, env, error::Error, fs, path::PathBuf};
+
+const TABLE_PATH: &str = "data/country_buckets.csv";
+const HEADER: &str = "code,bucket,subregion,name";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed={TABLE_PATH}");
+
+    let table = fs::read_to_string(TABLE_PATH)?;
+    let mut lines = table
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => return Err(format!("{TABLE_PATH}: expected header `{HEADER}`").into()),
+    }
+
+    let mut buckets = BTreeMap::new();
+    for (line_number, line) in lines {
+        let row_error = |message: String| format!("{TABLE_PATH}:{line_number}: {message}");
+        let mut fields = line.splitn(4, ',');
+        let (Some(code), Some(bucket), Some(_subregion), Some(_name)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(row_error(format!("expected `{HEADER}`")).into());
+        };
+        if code.len() != 2 || !code.bytes().all(|byte| byte.is_ascii_uppercase()) {
+            return Err(row_error(format!("invalid country code {code:?}")).into());
+        }
+        let variant = match bucket {
+            "UNKNOWN" => "Unknown",
+            "EU" => "Eu",
+            "NA" => "Na",
+            "APAC" => "Apac",
+            "ME" => "Me",
+            "LATAM" => "Latam",
+            "AFRICA" => "Africa",
+            "OCEANIA" => "Oceania",
+            _ => return Err(row_error(format!("unknown bucket {bucket:?}")).into()),
+        };
+        if buckets.insert(code, variant).is_some() {
+            return Err(row_error(format!("duplicate country code {code}")).into());
+        }
+    }
+
+    let mut generated = format!(
+        "/// Every ISO-3166 alpha-2 code in `{TABLE_PATH}` with its bucket, sorted by code.\n\
+         pub const COUNTRY_BUCKETS: [(&str, GeoBucket); {}] = [\n",
+        buckets.len()
+    );
+    for (code, variant) in buckets {
+        generated.push_str(&format!("    ({code:?}, GeoBucket::{variant}),\n"));
+    }
+    generated.push_str("];\n");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    fs::write(out_dir.join("country_buckets.rs"), generated)?;
+
+    Ok(())
+}
@@ -1,4 +1,11 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+mod regions;
+mod rules;
+
+pub use regions::{ALL_REGIONS, GLOBAL_EXECUTOR_ENDPOINT, REGION_CATALOG, Region, RegionInfo};
+pub use rules::{EMBEDDED_RULES_JSON, RuleSet, RuleSetError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum GeoBucket {
     Unknown = 0,
@@ -6,9 +13,23 @@ pub enum GeoBucket {
     Na = 2,
     Apac = 3,
     Me = 4,
+    Latam = 5,
+    Africa = 6,
+    Oceania = 7,
 }
 
 impl GeoBucket {
+    pub const ALL: [GeoBucket; 8] = [
+        GeoBucket::Unknown,
+        GeoBucket::Eu,
+        GeoBucket::Na,
+        GeoBucket::Apac,
+        GeoBucket::Me,
+        GeoBucket::Latam,
+        GeoBucket::Africa,
+        GeoBucket::Oceania,
+    ];
+
     pub fn as_u8(self) -> u8 {
         self as u8
     }
@@ -20,10 +41,30 @@ impl GeoBucket {
             2 => Some(Self::Na),
             3 => Some(Self::Apac),
             4 => Some(Self::Me),
+            5 => Some(Self::Latam),
+            6 => Some(Self::Africa),
+            7 => Some(Self::Oceania),
             _ => None,
         }
     }
 
+    /// Inverse of [`Self::label`].
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|bucket| bucket.label() == label)
+    }
+
+    /// The bucket among the original five (codes 0-4) that covers this one,
+    /// routed to the same region. Used where readers predating the newer
+    /// buckets only know those codes.
+    pub fn legacy_bucket(self) -> GeoBucket {
+        match self {
+            Self::Latam => Self::Na,
+            Self::Africa => Self::Eu,
+            Self::Oceania => Self::Apac,
+            bucket => bucket,
+        }
+    }
+
     pub fn label(self) -> &'static str {
         match self {
             Self::Unknown => "UNKNOWN",
@@ -31,51 +72,123 @@ impl GeoBucket {
             Self::Na => "NA",
             Self::Apac => "APAC",
             Self::Me => "ME",
+            Self::Latam => "LATAM",
+            Self::Africa => "AFRICA",
+            Self::Oceania => "OCEANIA",
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Region {
-    Dubai,
-    Frankfurt,
-    NewYork,
-    Tokyo,
+/// Rough round-trip time in milliseconds from a typical host in `bucket` to
+/// the Zela executor in `region`. `None` for `GeoBucket::Unknown`.
+pub fn estimated_rtt_ms(bucket: GeoBucket, region: Region) -> Option<u32> {
+    region
+        .info()
+        .estimated_rtt_ms
+        .iter()
+        .find(|(known, _)| *known == bucket)
+        .map(|(_, rtt_ms)| *rtt_ms)
 }
 
-pub fn bucket_from_country_iso(iso_code: &str) -> GeoBucket {
-    match iso_code.trim().to_ascii_uppercase().as_str() {
-        "DE" | "FR" | "NL" | "GB" | "CH" | "SE" | "NO" | "PL" | "ES" | "IT" => GeoBucket::Eu,
-        "AE" | "SA" | "IL" | "TR" | "QA" | "BH" | "OM" | "KW" => GeoBucket::Me,
-        "US" | "CA" | "MX" => GeoBucket::Na,
-        "JP" | "KR" | "SG" | "HK" | "TW" | "IN" | "AU" | "NZ" => GeoBucket::Apac,
-        _ => GeoBucket::Unknown,
+/// A point on the globe in decimal degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Mean Earth radius used by `distance_km`.
+    pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    pub const fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Whether the point is a valid latitude/longitude pair.
+    pub fn is_valid(self) -> bool {
+        (-90.0..=90.0).contains(&self.latitude) && (-180.0..=180.0).contains(&self.longitude)
     }
+
+    /// Great-circle distance (haversine formula).
+    pub fn distance_km(self, other: Coordinates) -> f64 {
+        let d_lat = (other.latitude - self.latitude).to_radians();
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos()
+                * other.latitude.to_radians().cos()
+                * (d_lon / 2.0).sin().powi(2);
+        2.0 * Self::EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// Location of the Zela datacenter serving `region`.
+pub fn region_coordinates(region: Region) -> Coordinates {
+    region.info().coordinates
+}
+
+/// All regions ordered by great-circle distance from `location`, nearest
+/// first. Ties keep `ALL_REGIONS` order.
+pub fn regions_ranked_by_distance(location: Coordinates) -> Vec<(Region, f64)> {
+    let mut ranked = ALL_REGIONS
+        .iter()
+        .map(|region| (*region, location.distance_km(region_coordinates(*region))))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|left, right| left.1.total_cmp(&right.1));
+    ranked
+}
+
+pub fn nearest_region(location: Coordinates) -> Region {
+    regions_ranked_by_distance(location)[0].0
+}
+
+include!(concat!(env!("OUT_DIR"), "/country_buckets.rs"));
+
+/// Bucket for an ISO-3166 alpha-2 code under the embedded rules: the table in
+/// `data/country_buckets.csv`, with the `countries` of `data/rules.json` on
+/// top. Unassigned codes are `GeoBucket::Unknown`.
+pub fn bucket_from_country_iso(iso_code: &str) -> GeoBucket {
+    RuleSet::embedded().bucket_for_country(iso_code)
 }
 
 pub fn bucket_from_geo_input(input: &str) -> GeoBucket {
     let normalized = input.trim().to_ascii_uppercase();
-    match normalized.as_str() {
-        "EU" => GeoBucket::Eu,
-        "NA" => GeoBucket::Na,
-        "APAC" => GeoBucket::Apac,
-        "ME" => GeoBucket::Me,
-        _ => bucket_from_country_iso(&normalized),
-    }
+    GeoBucket::from_label(&normalized).unwrap_or_else(|| bucket_from_country_iso(&normalized))
 }
 
+/// Most preferred region for `bucket` under the embedded rules.
 pub fn region_from_bucket(bucket: GeoBucket) -> Option<Region> {
-    match bucket {
-        GeoBucket::Eu => Some(Region::Frankfurt),
-        GeoBucket::Me => Some(Region::Dubai),
-        GeoBucket::Na => Some(Region::NewYork),
-        GeoBucket::Apac => Some(Region::Tokyo),
-        GeoBucket::Unknown => None,
-    }
+    RuleSet::embedded().region_for_bucket(bucket)
+}
+
+/// Regions serving `bucket` under the embedded rules, most preferred first,
+/// e.g. `ME` -> `[Dubai, Frankfurt, Tokyo, NewYork]`. Empty for
+/// `GeoBucket::Unknown`.
+pub fn region_preferences(bucket: GeoBucket) -> &'static [Region] {
+    RuleSet::embedded().regions_for_bucket(bucket)
 }
 
+/// [`region_preferences`] without the regions missing from `available`;
+/// see [`RuleSet::select_regions`].
+pub fn select_regions(
+    bucket: GeoBucket,
+    available: &[Region],
+) -> impl Iterator<Item = Region> + '_ {
+    RuleSet::embedded().select_regions(bucket, available)
+}
+
+/// Region forced for a country by the embedded rules' `region_overrides`.
+pub fn region_override_for_country(iso_code: &str) -> Option<Region> {
+    RuleSet::embedded().region_override(iso_code)
+}
+
+/// Region for a bucket label or country code under the embedded rules; see
+/// [`RuleSet::region_for_geo_input`].
 pub fn region_from_geo_input(input: &str) -> Option<Region> {
-    region_from_bucket(bucket_from_geo_input(input))
+    RuleSet::embedded().region_for_geo_input(input)
 }
 
 #[cfg(test)]
@@ -88,7 +201,54 @@ mod tests {
         assert_eq!(bucket_from_country_iso("US"), GeoBucket::Na);
         assert_eq!(bucket_from_country_iso("JP"), GeoBucket::Apac);
         assert_eq!(bucket_from_country_iso("AE"), GeoBucket::Me);
-        assert_eq!(bucket_from_country_iso("BR"), GeoBucket::Unknown);
+        assert_eq!(bucket_from_country_iso(" fi "), GeoBucket::Eu);
+        assert_eq!(bucket_from_country_iso("BR"), GeoBucket::Latam);
+        assert_eq!(bucket_from_country_iso("XX"), GeoBucket::Unknown);
+        assert_eq!(bucket_from_country_iso(""), GeoBucket::Unknown);
+    }
+
+    #[test]
+    fn country_buckets_cover_every_iso_code() {
+        // Antarctic and sub-Antarctic territories with no hosting.
+        const INTENTIONALLY_UNKNOWN: [&str; 5] = ["AQ", "BV", "GS", "HM", "TF"];
+
+        assert_eq!(COUNTRY_BUCKETS.len(), 249);
+        assert!(COUNTRY_BUCKETS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for (code, bucket) in COUNTRY_BUCKETS {
+            assert_eq!(bucket_from_country_iso(code), bucket, "{code}");
+            assert_eq!(
+                bucket_from_country_iso(&code.to_ascii_lowercase()),
+                bucket,
+                "{code}"
+            );
+            assert_eq!(
+                bucket == GeoBucket::Unknown,
+                INTENTIONALLY_UNKNOWN.contains(&code),
+                "{code} is {bucket:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn major_validator_hosting_countries_are_assigned() {
+        for (code, bucket) in [
+            ("FI", GeoBucket::Eu),
+            ("IE", GeoBucket::Eu),
+            ("LT", GeoBucket::Eu),
+            ("CZ", GeoBucket::Eu),
+            ("AT", GeoBucket::Eu),
+            ("RO", GeoBucket::Eu),
+            ("UA", GeoBucket::Eu),
+            ("BR", GeoBucket::Latam),
+            ("MX", GeoBucket::Latam),
+            ("ZA", GeoBucket::Africa),
+            ("NG", GeoBucket::Africa),
+            ("AU", GeoBucket::Oceania),
+            ("NZ", GeoBucket::Oceania),
+            ("VN", GeoBucket::Apac),
+        ] {
+            assert_eq!(bucket_from_country_iso(code), bucket, "{code}");
+        }
     }
 
     #[test]
@@ -97,6 +257,9 @@ mod tests {
         assert_eq!(bucket_from_geo_input("NA"), GeoBucket::Na);
         assert_eq!(bucket_from_geo_input("apac"), GeoBucket::Apac);
         assert_eq!(bucket_from_geo_input("ME"), GeoBucket::Me);
+        assert_eq!(bucket_from_geo_input("latam"), GeoBucket::Latam);
+        assert_eq!(bucket_from_geo_input("AFRICA"), GeoBucket::Africa);
+        assert_eq!(bucket_from_geo_input("Oceania"), GeoBucket::Oceania);
         assert_eq!(bucket_from_geo_input("UNKNOWN"), GeoBucket::Unknown);
     }
 
@@ -108,4 +271,96 @@ mod tests {
         assert_eq!(region_from_geo_input("JP"), Some(Region::Tokyo));
         assert_eq!(region_from_geo_input("unknown"), None);
     }
+
+    #[test]
+    fn region_preferences_start_with_rule_table_region() {
+        assert_eq!(
+            region_preferences(GeoBucket::Me),
+            [
+                Region::Dubai,
+                Region::Frankfurt,
+                Region::Tokyo,
+                Region::NewYork
+            ]
+        );
+        for bucket in GeoBucket::ALL {
+            assert_eq!(
+                region_preferences(bucket).first().copied(),
+                region_from_bucket(bucket)
+            );
+        }
+        assert_eq!(
+            select_regions(GeoBucket::Me, &[Region::Tokyo, Region::Frankfurt]).collect::<Vec<_>>(),
+            [Region::Frankfurt, Region::Tokyo]
+        );
+    }
+
+    #[test]
+    fn bucket_codes_are_stable() {
+        for (code, bucket, label) in [
+            (0, GeoBucket::Unknown, "UNKNOWN"),
+            (1, GeoBucket::Eu, "EU"),
+            (2, GeoBucket::Na, "NA"),
+            (3, GeoBucket::Apac, "APAC"),
+            (4, GeoBucket::Me, "ME"),
+            (5, GeoBucket::Latam, "LATAM"),
+            (6, GeoBucket::Africa, "AFRICA"),
+            (7, GeoBucket::Oceania, "OCEANIA"),
+        ] {
+            assert_eq!(bucket.as_u8(), code);
+            assert_eq!(GeoBucket::from_u8(code), Some(bucket));
+            assert_eq!(bucket.label(), label);
+            assert_eq!(GeoBucket::from_label(label), Some(bucket));
+            assert_eq!(bucket_from_geo_input(label), bucket);
+        }
+        assert_eq!(GeoBucket::from_u8(8), None);
+    }
+
+    #[test]
+    fn legacy_bucket_keeps_region() {
+        for bucket in GeoBucket::ALL {
+            let legacy = bucket.legacy_bucket();
+            assert!(legacy.as_u8() <= GeoBucket::Me.as_u8());
+            assert_eq!(region_from_bucket(legacy), region_from_bucket(bucket));
+        }
+        assert_eq!(GeoBucket::Latam.legacy_bucket(), GeoBucket::Na);
+    }
+
+    #[test]
+    fn distance_km_matches_known_city_pairs() {
+        let frankfurt = region_coordinates(Region::Frankfurt);
+        let new_york = region_coordinates(Region::NewYork);
+        let distance = frankfurt.distance_km(new_york);
+        assert!((6150.0..6250.0).contains(&distance), "{distance}");
+        assert_eq!(frankfurt.distance_km(frankfurt), 0.0);
+    }
+
+    #[test]
+    fn nearest_region_routes_by_real_distance() {
+        // Istanbul is in the ME bucket but much closer to Frankfurt than Dubai.
+        let istanbul = Coordinates::new(41.01, 28.98);
+        assert_eq!(region_from_geo_input("TR"), Some(Region::Dubai));
+        assert_eq!(nearest_region(istanbul), Region::Frankfurt);
+
+        assert_eq!(
+            nearest_region(Coordinates::new(52.23, 21.01)),
+            Region::Frankfurt
+        );
+        assert_eq!(
+            nearest_region(Coordinates::new(1.35, 103.82)),
+            Region::Tokyo
+        );
+        assert_eq!(
+            nearest_region(Coordinates::new(43.65, -79.38)),
+            Region::NewYork
+        );
+        assert_eq!(
+            nearest_region(Coordinates::new(19.08, 72.88)),
+            Region::Dubai
+        );
+
+        let ranked = regions_ranked_by_distance(istanbul);
+        assert_eq!(ranked.len(), ALL_REGIONS.len());
+        assert!(ranked.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
 }
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Coordinates, GeoBucket};
+
+/// The Zela executor endpoint. Regions without an endpoint of their own are
+/// served through it.
+pub const GLOBAL_EXECUTOR_ENDPOINT: &str = "https://executor.zela.io";
+
+/// Declares [`Region`], one variant per entry, together with
+/// [`REGION_CATALOG`] and [`Region::name`], so a region's name is written
+/// exactly once: as its variant, which is also its serde form.
+macro_rules! region_catalog {
+    ($($region:ident { $($field:ident: $value:expr),+ $(,)? }),+ $(,)?) => {
+        /// A Zela region. Serialized by name, e.g. `"NewYork"`; everything
+        /// else about the region lives in its [`REGION_CATALOG`] entry.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum Region {
+            $($region),+
+        }
+
+        /// Every region, in `Region` declaration order. Adding a region means
+        /// adding its entry here.
+        pub const REGION_CATALOG: &[RegionInfo] = &[
+            $(RegionInfo { region: Region::$region, $($field: $value),+ }),+
+        ];
+
+        impl Region {
+            /// Stable identifier used in rules files, params and procedure
+            /// output; the serialized form of the region.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$region => stringify!($region)),+
+                }
+            }
+        }
+    };
+}
+
+/// Catalog entry for one region.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RegionInfo {
+    pub region: Region,
+    pub display_name: &'static str,
+    /// Zela executor endpoint for calls pinned to this region.
+    pub endpoint: &'static str,
+    /// Location of the datacenter.
+    pub coordinates: Coordinates,
+    /// IANA time zone of the datacenter.
+    pub timezone: &'static str,
+    /// Nearest AWS, GCP and Azure regions, for clients co-locating with it.
+    pub cloud_region_ids: &'static [&'static str],
+    /// Rough round-trip time in milliseconds from a typical host in each
+    /// bucket to this region.
+    #[serde(skip)]
+    pub estimated_rtt_ms: &'static [(GeoBucket, u32)],
+}
+
+region_catalog! {
+    Dubai {
+        display_name: "Dubai",
+        endpoint: GLOBAL_EXECUTOR_ENDPOINT,
+        coordinates: Coordinates::new(25.20, 55.27),
+        timezone: "Asia/Dubai",
+        cloud_region_ids: &["me-central-1", "uaenorth"],
+        estimated_rtt_ms: &[
+            (GeoBucket::Eu, 110),
+            (GeoBucket::Na, 200),
+            (GeoBucket::Apac, 120),
+            (GeoBucket::Me, 15),
+            (GeoBucket::Latam, 300),
+            (GeoBucket::Africa, 170),
+            (GeoBucket::Oceania, 180),
+        ],
+    },
+    Frankfurt {
+        display_name: "Frankfurt",
+        endpoint: GLOBAL_EXECUTOR_ENDPOINT,
+        coordinates: Coordinates::new(50.11, 8.68),
+        timezone: "Europe/Berlin",
+        cloud_region_ids: &["eu-central-1", "europe-west3", "germanywestcentral"],
+        estimated_rtt_ms: &[
+            (GeoBucket::Eu, 15),
+            (GeoBucket::Na, 95),
+            (GeoBucket::Apac, 200),
+            (GeoBucket::Me, 110),
+            (GeoBucket::Latam, 200),
+            (GeoBucket::Africa, 150),
+            (GeoBucket::Oceania, 260),
+        ],
+    },
+    NewYork {
+        display_name: "New York",
+        endpoint: GLOBAL_EXECUTOR_ENDPOINT,
+        coordinates: Coordinates::new(40.71, -74.01),
+        timezone: "America/New_York",
+        cloud_region_ids: &["us-east-1", "us-east4", "eastus"],
+        estimated_rtt_ms: &[
+            (GeoBucket::Eu, 90),
+            (GeoBucket::Na, 20),
+            (GeoBucket::Apac, 190),
+            (GeoBucket::Me, 190),
+            (GeoBucket::Latam, 120),
+            (GeoBucket::Africa, 230),
+            (GeoBucket::Oceania, 200),
+        ],
+    },
+    Tokyo {
+        display_name: "Tokyo",
+        endpoint: GLOBAL_EXECUTOR_ENDPOINT,
+        coordinates: Coordinates::new(35.68, 139.69),
+        timezone: "Asia/Tokyo",
+        cloud_region_ids: &["ap-northeast-1", "asia-northeast1", "japaneast"],
+        estimated_rtt_ms: &[
+            (GeoBucket::Eu, 230),
+            (GeoBucket::Na, 160),
+            (GeoBucket::Apac, 60),
+            (GeoBucket::Me, 150),
+            (GeoBucket::Latam, 260),
+            (GeoBucket::Africa, 330),
+            (GeoBucket::Oceania, 110),
+        ],
+    },
+}
+
+/// Every region in catalog order.
+pub const ALL_REGIONS: [Region; REGION_CATALOG.len()] = {
+    let mut regions = [Region::Dubai; REGION_CATALOG.len()];
+    let mut index = 0;
+    while index < regions.len() {
+        regions[index] = REGION_CATALOG[index].region;
+        index += 1;
+    }
+    regions
+};
+
+impl Region {
+    pub fn info(self) -> &'static RegionInfo {
+        &REGION_CATALOG[self as usize]
+    }
+
+    /// Inverse of [`Self::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_REGIONS.into_iter().find(|region| region.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog_is_in_declaration_order() {
+        for (index, info) in REGION_CATALOG.iter().enumerate() {
+            assert_eq!(info.region as usize, index);
+            assert_eq!(info.region.info(), info);
+            assert_eq!(ALL_REGIONS[index], info.region);
+        }
+    }
+
+    #[test]
+    fn catalog_entries_are_complete() {
+        for info in REGION_CATALOG {
+            assert!(info.coordinates.is_valid(), "{:?}", info.region);
+            assert!(info.endpoint.starts_with("https://"), "{:?}", info.region);
+            assert!(!info.cloud_region_ids.is_empty(), "{:?}", info.region);
+            for bucket in &GeoBucket::ALL[1..] {
+                assert_eq!(
+                    info.estimated_rtt_ms
+                        .iter()
+                        .filter(|(known, _)| known == bucket)
+                        .count(),
+                    1,
+                    "{:?} {bucket:?}",
+                    info.region
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn regions_serialize_by_name() {
+        for region in ALL_REGIONS {
+            let json = serde_json::to_value(region).unwrap();
+            assert_eq!(json, serde_json::json!(region.name()));
+            assert_eq!(serde_json::from_value::<Region>(json).unwrap(), region);
+            assert_eq!(Region::from_name(region.name()), Some(region));
+        }
+        assert_eq!(Region::from_name("frankfurt"), None);
+
+        let info = serde_json::to_value(Region::Frankfurt.info()).unwrap();
+        assert_eq!(info["region"], "Frankfurt");
+        assert_eq!(info["display_name"], "Frankfurt");
+        assert_eq!(info["timezone"], "Europe/Berlin");
+        assert_eq!(info["cloud_region_ids"][0], "eu-central-1");
+        assert_eq!(info["coordinates"]["latitude"], 50.11);
+        assert!(info.get("estimated_rtt_ms").is_none());
+    }
+}
@@ -0,0 +1,338 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{COUNTRY_BUCKETS, GeoBucket, Region};
+
+/// The rules compiled into this crate, `data/rules.json`.
+pub const EMBEDDED_RULES_JSON: &str = include_str!("../data/rules.json");
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RuleSetError {
+    #[error("rules are not valid JSON: {0}")]
+    Parse(String),
+    #[error("unknown bucket {0:?}")]
+    UnknownBucket(String),
+    #[error("unknown region {0:?}")]
+    UnknownRegion(String),
+    #[error("{0:?} is not an upper-case ISO-3166 alpha-2 country code")]
+    InvalidCountry(String),
+    #[error("bucket {0} has no regions")]
+    MissingRegions(&'static str),
+    #[error("bucket {bucket} lists region {region} more than once")]
+    DuplicateRegion {
+        bucket: &'static str,
+        region: &'static str,
+    },
+    #[error("UNKNOWN leaders use the hash fallback and can't be given regions")]
+    UnknownBucketRouted,
+}
+
+/// On-disk shape of a rules file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    /// Country -> bucket, replacing the entry in `data/country_buckets.csv`.
+    #[serde(default)]
+    countries: BTreeMap<String, String>,
+    /// Bucket -> regions, most preferred first.
+    bucket_regions: BTreeMap<String, Vec<String>>,
+    /// Country -> region, bypassing the country's bucket.
+    #[serde(default)]
+    region_overrides: BTreeMap<String, String>,
+}
+
+/// Validated routing policy: which bucket a country is in, which regions
+/// serve a bucket, and per-country region overrides.
+///
+/// Countries without an entry in the rules keep their bucket from the
+/// generated `data/country_buckets.csv` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    countries: HashMap<String, GeoBucket>,
+    bucket_regions: HashMap<GeoBucket, Vec<Region>>,
+    region_overrides: HashMap<String, Region>,
+}
+
+impl RuleSet {
+    /// Parses and validates a rules file. Every bucket but `UNKNOWN` needs at
+    /// least one region.
+    pub fn from_json(json: &str) -> Result<Self, RuleSetError> {
+        let file: RulesFile =
+            serde_json::from_str(json).map_err(|err| RuleSetError::Parse(err.to_string()))?;
+
+        let mut countries = HashMap::new();
+        for (country, bucket) in file.countries {
+            let bucket = parse_bucket(&bucket)?;
+            countries.insert(parse_country(country)?, bucket);
+        }
+
+        let mut bucket_regions = HashMap::new();
+        for (bucket, regions) in file.bucket_regions {
+            let bucket = parse_bucket(&bucket)?;
+            if bucket == GeoBucket::Unknown {
+                return Err(RuleSetError::UnknownBucketRouted);
+            }
+            let mut parsed: Vec<Region> = Vec::with_capacity(regions.len());
+            for region in regions {
+                let region = parse_region(&region)?;
+                if parsed.contains(&region) {
+                    return Err(RuleSetError::DuplicateRegion {
+                        bucket: bucket.label(),
+                        region: region.name(),
+                    });
+                }
+                parsed.push(region);
+            }
+            bucket_regions.insert(bucket, parsed);
+        }
+        for bucket in GeoBucket::ALL {
+            let routed = bucket_regions
+                .get(&bucket)
+                .is_some_and(|regions| !regions.is_empty());
+            if bucket != GeoBucket::Unknown && !routed {
+                return Err(RuleSetError::MissingRegions(bucket.label()));
+            }
+        }
+
+        let mut region_overrides = HashMap::new();
+        for (country, region) in file.region_overrides {
+            let region = parse_region(&region)?;
+            region_overrides.insert(parse_country(country)?, region);
+        }
+
+        Ok(Self {
+            countries,
+            bucket_regions,
+            region_overrides,
+        })
+    }
+
+    /// The rules compiled into this crate. `procedure`'s build script rejects
+    /// invalid embedded rules, so this only panics on a broken build.
+    pub fn embedded() -> &'static RuleSet {
+        static RULES: OnceLock<RuleSet> = OnceLock::new();
+        RULES.get_or_init(|| {
+            RuleSet::from_json(EMBEDDED_RULES_JSON).expect("embedded data/rules.json is valid")
+        })
+    }
+
+    /// Bucket for an ISO-3166 alpha-2 code (any case). Unassigned codes are
+    /// `GeoBucket::Unknown`.
+    pub fn bucket_for_country(&self, iso_code: &str) -> GeoBucket {
+        let iso_code = iso_code.trim().to_ascii_uppercase();
+        if let Some(bucket) = self.countries.get(&iso_code) {
+            return *bucket;
+        }
+        COUNTRY_BUCKETS
+            .binary_search_by_key(&iso_code.as_str(), |(code, _)| code)
+            .map_or(GeoBucket::Unknown, |index| COUNTRY_BUCKETS[index].1)
+    }
+
+    /// Regions serving `bucket`, most preferred first. Empty for
+    /// `GeoBucket::Unknown`.
+    pub fn regions_for_bucket(&self, bucket: GeoBucket) -> &[Region] {
+        self.bucket_regions.get(&bucket).map_or(&[], Vec::as_slice)
+    }
+
+    /// The most preferred region for `bucket`.
+    pub fn region_for_bucket(&self, bucket: GeoBucket) -> Option<Region> {
+        self.regions_for_bucket(bucket).first().copied()
+    }
+
+    /// Walks the preferences for `bucket`, skipping regions not in
+    /// `available`. The first item is the primary region, the next one the
+    /// failover.
+    pub fn select_regions<'a>(
+        &'a self,
+        bucket: GeoBucket,
+        available: &'a [Region],
+    ) -> impl Iterator<Item = Region> + 'a {
+        self.regions_for_bucket(bucket)
+            .iter()
+            .copied()
+            .filter(|region| available.contains(region))
+    }
+
+    /// Region for a bucket label or country code (any case). Bucket labels
+    /// win over the countries spelled the same (`ME` is Montenegro, `NA`
+    /// Namibia), so a country's override never re-routes a whole bucket; for
+    /// countries, the override wins over the country's bucket.
+    pub fn region_for_geo_input(&self, input: &str) -> Option<Region> {
+        let normalized = input.trim().to_ascii_uppercase();
+        if let Some(bucket) = GeoBucket::from_label(&normalized) {
+            return self.region_for_bucket(bucket);
+        }
+        self.region_override(&normalized)
+            .or_else(|| self.region_for_bucket(self.bucket_for_country(&normalized)))
+    }
+
+    /// Region forced for a country regardless of its bucket, if any.
+    pub fn region_override(&self, iso_code: &str) -> Option<Region> {
+        self.region_overrides
+            .get(&iso_code.trim().to_ascii_uppercase())
+            .copied()
+    }
+}
+
+fn parse_bucket(label: &str) -> Result<GeoBucket, RuleSetError> {
+    GeoBucket::from_label(label).ok_or_else(|| RuleSetError::UnknownBucket(label.to_string()))
+}
+
+fn parse_region(name: &str) -> Result<Region, RuleSetError> {
+    Region::from_name(name).ok_or_else(|| RuleSetError::UnknownRegion(name.to_string()))
+}
+
+fn parse_country(code: String) -> Result<String, RuleSetError> {
+    if code.len() == 2 && code.bytes().all(|byte| byte.is_ascii_uppercase()) {
+        Ok(code)
+    } else {
+        Err(RuleSetError::InvalidCountry(code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL_RULES: &str = r#"{
+        "bucket_regions": {
+            "EU": ["Frankfurt"], "NA": ["NewYork"], "APAC": ["Tokyo"], "ME": ["Dubai"],
+            "LATAM": ["NewYork"], "AFRICA": ["Frankfurt"], "OCEANIA": ["Tokyo"]
+        }
+    }"#;
+
+    #[test]
+    fn embedded_rules_are_valid() {
+        let rules = RuleSet::from_json(EMBEDDED_RULES_JSON).unwrap();
+        assert_eq!(&rules, RuleSet::embedded());
+
+        assert!(rules.regions_for_bucket(GeoBucket::Unknown).is_empty());
+        assert_eq!(rules.region_for_bucket(GeoBucket::Unknown), None);
+    }
+
+    #[test]
+    fn rules_override_countries_and_regions() {
+        let rules = RuleSet::from_json(
+            r#"{
+                "countries": {"BR": "NA", "DE": "UNKNOWN"},
+                "bucket_regions": {
+                    "EU": ["Frankfurt", "Dubai"], "NA": ["NewYork"], "APAC": ["Tokyo"],
+                    "ME": ["Dubai"], "LATAM": ["NewYork"], "AFRICA": ["Dubai", "Frankfurt"],
+                    "OCEANIA": ["Tokyo"]
+                },
+                "region_overrides": {"TR": "Frankfurt"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.bucket_for_country("br"), GeoBucket::Na);
+        assert_eq!(rules.bucket_for_country("DE"), GeoBucket::Unknown);
+        assert_eq!(rules.bucket_for_country("FR"), GeoBucket::Eu);
+        assert_eq!(
+            rules.region_for_bucket(GeoBucket::Africa),
+            Some(Region::Dubai)
+        );
+        assert_eq!(
+            rules.regions_for_bucket(GeoBucket::Eu),
+            [Region::Frankfurt, Region::Dubai]
+        );
+        assert_eq!(rules.region_override("tr"), Some(Region::Frankfurt));
+        assert_eq!(rules.region_override("AE"), None);
+    }
+
+    #[test]
+    fn country_overrides_never_apply_to_bucket_labels() {
+        let json = MINIMAL_RULES.trim_end().trim_end_matches('}');
+        let rules = RuleSet::from_json(&format!(
+            r#"{json}, "region_overrides": {{"ME": "Tokyo", "NA": "Dubai", "TR": "Frankfurt"}} }}"#
+        ))
+        .unwrap();
+
+        assert_eq!(rules.region_for_geo_input("ME"), Some(Region::Dubai));
+        assert_eq!(rules.region_for_geo_input(" me "), Some(Region::Dubai));
+        assert_eq!(rules.region_for_geo_input("NA"), Some(Region::NewYork));
+        assert_eq!(rules.region_for_geo_input("TR"), Some(Region::Frankfurt));
+        assert_eq!(rules.region_for_geo_input("AE"), Some(Region::Dubai));
+        assert_eq!(rules.region_for_geo_input("UNKNOWN"), None);
+        // The countries themselves keep their override.
+        assert_eq!(rules.region_override("ME"), Some(Region::Tokyo));
+    }
+
+    #[test]
+    fn select_regions_skips_unavailable_regions() {
+        let rules = RuleSet::embedded();
+        let available = [Region::Frankfurt, Region::NewYork, Region::Tokyo];
+
+        assert_eq!(
+            rules
+                .select_regions(GeoBucket::Me, &available)
+                .collect::<Vec<_>>(),
+            [Region::Frankfurt, Region::Tokyo, Region::NewYork]
+        );
+        assert_eq!(
+            rules.select_regions(GeoBucket::Eu, &[Region::Tokyo]).next(),
+            Some(Region::Tokyo)
+        );
+        assert_eq!(rules.select_regions(GeoBucket::Eu, &[]).next(), None);
+        assert_eq!(
+            rules.select_regions(GeoBucket::Unknown, &available).next(),
+            None
+        );
+    }
+
+    #[test]
+    fn from_json_rejects_invalid_rules() {
+        let with = |field: &str| {
+            let json = MINIMAL_RULES.trim_end().trim_end_matches('}');
+            RuleSet::from_json(&format!("{json}, {field} }}")).unwrap_err()
+        };
+
+        assert_eq!(RuleSet::from_json(MINIMAL_RULES).map(|_| ()), Ok(()));
+        assert!(matches!(
+            RuleSet::from_json("{"),
+            Err(RuleSetError::Parse(_))
+        ));
+        assert!(matches!(
+            with(r#""region_override": {}"#),
+            RuleSetError::Parse(_)
+        ));
+        assert_eq!(
+            with(r#""countries": {"DE": "EUROPE"}"#),
+            RuleSetError::UnknownBucket("EUROPE".to_string())
+        );
+        assert_eq!(
+            with(r#""countries": {"DEU": "EU"}"#),
+            RuleSetError::InvalidCountry("DEU".to_string())
+        );
+        assert_eq!(
+            with(r#""region_overrides": {"TR": "Istanbul"}"#),
+            RuleSetError::UnknownRegion("Istanbul".to_string())
+        );
+        assert_eq!(
+            RuleSet::from_json(r#"{"bucket_regions": {"EU": ["Frankfurt"]}}"#),
+            Err(RuleSetError::MissingRegions("NA"))
+        );
+        assert_eq!(
+            RuleSet::from_json(&MINIMAL_RULES.replace(r#"["Tokyo"], "ME""#, r#"[], "ME""#)),
+            Err(RuleSetError::MissingRegions("APAC"))
+        );
+        assert_eq!(
+            RuleSet::from_json(&MINIMAL_RULES.replace(r#"["Dubai"]"#, r#"["Dubai", "Dubai"]"#)),
+            Err(RuleSetError::DuplicateRegion {
+                bucket: "ME",
+                region: "Dubai"
+            })
+        );
+        assert_eq!(
+            RuleSet::from_json(
+                &MINIMAL_RULES.replace(r#""EU":"#, r#""UNKNOWN": ["Tokyo"], "EU":"#)
+            ),
+            Err(RuleSetError::UnknownBucketRouted)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Lookup throughput of the map formats.
+//!
+//! `cargo bench -p leader-geo-map` builds a map the size of the embedded one
+//! from synthetic pubkeys and times a shuffled mix of hits and misses, both
+//! against a parsed view and with a `parse` per lookup as the procedure does.
+//! `LEADER_GEO_MAP_BENCH_LEADERS` sets another map size.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use geo_rules::GeoBucket;
+use leader_geo_map::{DEFAULT_KEY_PREFIX_SIZE, LeaderGeoMap, LeaderGeoMapBuilder};
+
+const DEFAULT_LEADERS: usize = 5_146;
+const MISSES: usize = 1_000;
+const ROUNDS: usize = 200;
+
+fn main() {
+    let leader_count = std::env::var("LEADER_GEO_MAP_BENCH_LEADERS")
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .expect("LEADER_GEO_MAP_BENCH_LEADERS is a count")
+        })
+        .unwrap_or(DEFAULT_LEADERS);
+    let mut rng = SplitMix64(0x5eed);
+    let leaders = (0..leader_count).map(|_| rng.pubkey()).collect::<Vec<_>>();
+    let mut queries = leaders.clone();
+    queries.extend((0..MISSES).map(|_| rng.pubkey()));
+    for index in (1..queries.len()).rev() {
+        queries.swap(index, rng.below(index + 1));
+    }
+
+    let builder = leaders
+        .iter()
+        .enumerate()
+        .map(|(index, pubkey)| (*pubkey, GeoBucket::ALL[index % GeoBucket::ALL.len()]))
+        .collect::<LeaderGeoMapBuilder>();
+
+    let formats = [
+        ("v2, 32-byte keys", builder.build().unwrap()),
+        (
+            "v3, 8-byte keys",
+            builder.build_compact(DEFAULT_KEY_PREFIX_SIZE).unwrap(),
+        ),
+    ];
+
+    println!(
+        "{leader_count} leaders, {} queries ({MISSES} misses) x {ROUNDS} rounds",
+        queries.len()
+    );
+    println!(
+        "{:<28} {:>10} {:>14} {:>18}",
+        "format", "bytes", "ns/lookup", "ns/parse+lookup"
+    );
+    for (name, bytes) in &formats {
+        let map = LeaderGeoMap::parse(bytes).unwrap();
+        let lookup = time_per_query(&queries, leader_count, |pubkey| map.get(pubkey));
+        let parse_and_lookup = time_per_query(&queries, leader_count, |pubkey| {
+            LeaderGeoMap::parse(black_box(bytes)).ok()?.get(pubkey)
+        });
+        println!(
+            "{name:<28} {:>10} {:>14.1} {:>18.1}",
+            bytes.len(),
+            nanos(lookup),
+            nanos(parse_and_lookup)
+        );
+    }
+}
+
+fn time_per_query(
+    queries: &[[u8; 32]],
+    leader_count: usize,
+    lookup: impl Fn(&[u8; 32]) -> Option<u8>,
+) -> Duration {
+    // Warm-up round, also a sanity check that every format sees the same hits.
+    let hits = queries
+        .iter()
+        .filter(|pubkey| lookup(pubkey).is_some())
+        .count();
+    assert_eq!(hits, leader_count);
+
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        for pubkey in queries {
+            black_box(lookup(black_box(pubkey)));
+        }
+    }
+    started.elapsed() / (ROUNDS * queries.len()) as u32
+}
+
+fn nanos(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e9
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn pubkey(&mut self) -> [u8; 32] {
+        let mut pubkey = [0u8; 32];
+        for chunk in pubkey.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&self.next().to_le_bytes());
+        }
+        pubkey
+    }
+}
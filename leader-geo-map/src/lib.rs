@@ -0,0 +1,1039 @@
+//! Reader and writer for the `leader_pubkey -> geo bucket` map that
+//! `geo-mapper` generates and the procedure embeds.
+//!
+//! v2 layout (all integers little-endian):
+//!
+//! ```text
+//! magic "LGEO" | version u16 | record_size u16 | record_count u32
+//! label_count u8 | label_count x (bucket u8, label_len u8, label bytes)
+//! record_count x [pubkey 32 bytes][bucket 1 byte][extra bytes], sorted by pubkey
+//! checksum u64 = fnv1a64 of every preceding byte
+//! ```
+//!
+//! Optional per-record fields follow the bucket. Which ones are present is
+//! listed by a label table entry with the reserved code `FIELDS_ENTRY_CODE`
+//! (255) whose text is the comma-separated field names in record order, e.g.
+//! `country,coordinates`. Bytes after the last listed field, and all bytes
+//! after the bucket when there is no such entry, are opaque extensions that
+//! readers skip. Readers stop at the first field name they don't know.
+//!
+//! - `country` (2 bytes): ISO-3166 alpha-2 in ASCII, or two zero bytes when
+//!   the country is unknown. Records are `RECORD_SIZE_WITH_COUNTRY` (35).
+//! - `coordinates` (4 bytes): `[latitude i16][longitude i16]` in hundredths
+//!   of a degree (about 1 km), both `i16::MIN` when the location is unknown.
+//!   Always written after the country, for `RECORD_SIZE_WITH_COORDINATES`
+//!   (39) byte records.
+//! - `bucket` (1 byte): the leader's bucket when it is newer than the
+//!   original five (`LATAM`, `AFRICA`, `OCEANIA`). The bucket byte itself
+//!   always holds [`GeoBucket::legacy_bucket`], which readers predating those
+//!   buckets route on. Written last, so those readers still find the country
+//!   and coordinates where they expect them.
+//!
+//! v3 is v2 with truncated keys: a `key_size u8` sits between `record_count`
+//! and `label_count`, and each record starts with the first `key_size` bytes
+//! of the pubkey instead of all 32. The builder rejects prefixes that are not
+//! unique among the stored leaders; a pubkey that is not in the map but
+//! shares a stored prefix still matches, which at 8 bytes and a few thousand
+//! leaders has a chance of about 1e-16 per lookup.
+//!
+//! v1 is a bare sequence of 33-byte records and is still readable.
+
+use std::collections::BTreeMap;
+
+use geo_rules::{Coordinates, GeoBucket};
+use thiserror::Error;
+
+pub const MAGIC: &[u8; 4] = b"LGEO";
+pub const SCHEMA_VERSION_V1: u16 = 1;
+pub const SCHEMA_VERSION_V2: u16 = 2;
+pub const SCHEMA_VERSION_V3: u16 = 3;
+/// Key prefix length `geo-mapper` uses for compact (v3) maps by default.
+pub const DEFAULT_KEY_PREFIX_SIZE: usize = 8;
+pub const RECORD_SIZE: usize = 33;
+pub const RECORD_SIZE_WITH_COUNTRY: usize = 35;
+pub const RECORD_SIZE_WITH_COORDINATES: usize = 39;
+pub const CHECKSUM_SIZE: usize = 8;
+/// Label table code of the entry listing the optional record fields; never
+/// a bucket code.
+pub const FIELDS_ENTRY_CODE: u8 = 255;
+
+const PUBKEY_SIZE: usize = 32;
+const FIXED_HEADER_SIZE: usize = 13;
+const FIXED_HEADER_SIZE_V3: usize = 14;
+/// Record bytes after the key: bucket, then the optional fields.
+const PAYLOAD_SIZE: usize = RECORD_SIZE - PUBKEY_SIZE;
+const COUNTRY_FIELD: &str = "country";
+const COORDINATES_FIELD: &str = "coordinates";
+const BUCKET_FIELD: &str = "bucket";
+const COUNTRY_SIZE: usize = 2;
+const COORDINATES_SIZE: usize = 4;
+const BUCKET_SIZE: usize = 1;
+const COORDINATE_SCALE: f64 = 100.0;
+const NO_COORDINATE: i16 = i16::MIN;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LeaderGeoMapError {
+    #[error("map length {len} is not a multiple of the {RECORD_SIZE}-byte record size")]
+    Misaligned { len: usize },
+    #[error("map header is truncated")]
+    TruncatedHeader,
+    #[error("unsupported map schema version {0}")]
+    UnsupportedVersion(u16),
+    #[error("record size {0} is too small for the map's key size and fields")]
+    RecordSizeTooSmall(usize),
+    #[error("key size {0} is not between 1 and 32 bytes")]
+    InvalidKeySize(usize),
+    #[error("{key_size}-byte key prefixes collide; the shortest unique prefix is {shortest} bytes")]
+    KeyPrefixCollision { key_size: usize, shortest: usize },
+    #[error("bucket label for code {code} is not valid UTF-8")]
+    InvalidLabel { code: u8 },
+    #[error("map is {actual} bytes but its header describes {expected} bytes")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("record {index} has unknown bucket byte {bucket}")]
+    UnknownBucket { index: usize, bucket: u8 },
+    #[error("record {index} is not strictly greater than the record before it")]
+    UnsortedRecords { index: usize },
+    #[error("checksum mismatch: header says {expected:#018x}, content hashes to {actual:#018x}")]
+    ChecksumMismatch { expected: u64, actual: u64 },
+    #[error("map has more than {} records", u32::MAX)]
+    TooManyRecords,
+    #[error("{0:?} is not an ISO-3166 alpha-2 country code")]
+    InvalidCountry(String),
+    #[error("coordinates ({latitude}, {longitude}) are out of range")]
+    InvalidCoordinates { latitude: f64, longitude: f64 },
+}
+
+/// One decoded map record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeaderGeoRecord<'a> {
+    /// Bucket code as written by the map generator: the `bucket` field when
+    /// the map has one, otherwise the bucket byte.
+    pub bucket: u8,
+    /// Upper-case ISO-3166 alpha-2 code, when the map stores countries and
+    /// the leader's country was resolved.
+    pub country: Option<&'a str>,
+    /// Quantized leader location, when the map stores coordinates and the
+    /// leader's location was resolved.
+    pub coordinates: Option<Coordinates>,
+}
+
+/// Zero-copy view over an encoded map.
+///
+/// [`LeaderGeoMap::parse`] only checks the structure (header and lengths), so
+/// it is cheap enough to call per lookup. [`LeaderGeoMap::validate`] walks
+/// every record and the checksum.
+#[derive(Debug, Clone, Copy)]
+pub struct LeaderGeoMap<'a> {
+    bytes: &'a [u8],
+    version: u16,
+    labels: &'a [u8],
+    records: &'a [u8],
+    record_size: usize,
+    key_size: usize,
+    fields: RecordFields,
+}
+
+/// Offsets of the optional fields inside a record, counted from the bucket
+/// byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct RecordFields {
+    country: Option<usize>,
+    coordinates: Option<usize>,
+    bucket: Option<usize>,
+}
+
+impl RecordFields {
+    /// Reads the field list of the `FIELDS_ENTRY_CODE` label entry. Returns
+    /// the fields and the record bytes they take after the bucket.
+    fn parse(labels: &[u8]) -> (Self, usize) {
+        let mut fields = Self::default();
+        let mut offset = PAYLOAD_SIZE;
+        let Some(list) = label_entries(labels)
+            .find(|(code, _)| *code == FIELDS_ENTRY_CODE)
+            .and_then(|(_, list)| std::str::from_utf8(list).ok())
+        else {
+            return (fields, offset);
+        };
+        for name in list.split(',') {
+            let slot = match name {
+                COUNTRY_FIELD => &mut fields.country,
+                COORDINATES_FIELD => &mut fields.coordinates,
+                BUCKET_FIELD => &mut fields.bucket,
+                _ => break,
+            };
+            if slot.is_some() {
+                break;
+            }
+            *slot = Some(offset);
+            offset += field_size(name);
+        }
+        (fields, offset)
+    }
+}
+
+impl<'a> LeaderGeoMap<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, LeaderGeoMapError> {
+        if bytes.starts_with(MAGIC) {
+            let v2 = Self::parse_v2(bytes);
+            // A v1 map whose first pubkey happens to start with the magic.
+            if v2.is_err() && bytes.len().is_multiple_of(RECORD_SIZE) {
+                return Self::parse_v1(bytes);
+            }
+            return v2;
+        }
+        Self::parse_v1(bytes)
+    }
+
+    fn parse_v1(bytes: &'a [u8]) -> Result<Self, LeaderGeoMapError> {
+        if !bytes.len().is_multiple_of(RECORD_SIZE) {
+            return Err(LeaderGeoMapError::Misaligned { len: bytes.len() });
+        }
+        Ok(Self {
+            bytes,
+            version: SCHEMA_VERSION_V1,
+            labels: &[],
+            records: bytes,
+            record_size: RECORD_SIZE,
+            key_size: PUBKEY_SIZE,
+            fields: RecordFields::default(),
+        })
+    }
+
+    /// Parses v2 and v3, which only differ by the key size header byte.
+    fn parse_v2(bytes: &'a [u8]) -> Result<Self, LeaderGeoMapError> {
+        let header = bytes
+            .get(..FIXED_HEADER_SIZE)
+            .ok_or(LeaderGeoMapError::TruncatedHeader)?;
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        let record_size = usize::from(u16::from_le_bytes([header[6], header[7]]));
+        let record_count = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+
+        let (key_size, header_size) = match version {
+            SCHEMA_VERSION_V2 => (PUBKEY_SIZE, FIXED_HEADER_SIZE),
+            SCHEMA_VERSION_V3 => (usize::from(header[12]), FIXED_HEADER_SIZE_V3),
+            _ => return Err(LeaderGeoMapError::UnsupportedVersion(version)),
+        };
+        let label_count = *bytes
+            .get(header_size - 1)
+            .ok_or(LeaderGeoMapError::TruncatedHeader)?;
+
+        if !(1..=PUBKEY_SIZE).contains(&key_size) {
+            return Err(LeaderGeoMapError::InvalidKeySize(key_size));
+        }
+        let mut labels_end = header_size;
+        for _ in 0..label_count {
+            let label_len = *bytes
+                .get(labels_end + 1)
+                .ok_or(LeaderGeoMapError::TruncatedHeader)?;
+            labels_end += 2 + usize::from(label_len);
+        }
+        let labels = bytes
+            .get(header_size..labels_end)
+            .ok_or(LeaderGeoMapError::TruncatedHeader)?;
+
+        let (fields, payload_size) = RecordFields::parse(labels);
+        if record_size < key_size + payload_size {
+            return Err(LeaderGeoMapError::RecordSizeTooSmall(record_size));
+        }
+
+        let records_len = usize::try_from(record_count)
+            .ok()
+            .and_then(|count| count.checked_mul(record_size))
+            .ok_or(LeaderGeoMapError::TooManyRecords)?;
+        let expected = labels_end + records_len + CHECKSUM_SIZE;
+        if bytes.len() != expected {
+            return Err(LeaderGeoMapError::LengthMismatch {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        Ok(Self {
+            bytes,
+            version,
+            labels,
+            records: &bytes[labels_end..labels_end + records_len],
+            record_size,
+            key_size,
+            fields,
+        })
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn record_size(&self) -> usize {
+        self.record_size
+    }
+
+    /// Stored pubkey bytes per record: 32, or the prefix length for v3.
+    pub fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len() / self.record_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Whether records carry the leader's country, per the fields entry.
+    pub fn has_country(&self) -> bool {
+        self.fields.country.is_some()
+    }
+
+    /// Whether records carry the leader's coordinates, per the fields entry.
+    pub fn has_coordinates(&self) -> bool {
+        self.fields.coordinates.is_some()
+    }
+
+    /// Looks up `pubkey`; returns its bucket code (see
+    /// [`LeaderGeoRecord::bucket`]). Truncated (v3) maps compare only the
+    /// stored key prefix.
+    pub fn get(&self, pubkey: &[u8; 32]) -> Option<u8> {
+        self.find(pubkey).map(|index| self.bucket(index))
+    }
+
+    /// Looks up `pubkey`; returns the whole record.
+    pub fn get_record(&self, pubkey: &[u8; 32]) -> Option<LeaderGeoRecord<'a>> {
+        let index = self.find(pubkey)?;
+        Some(LeaderGeoRecord {
+            bucket: self.bucket(index),
+            country: self.country(index),
+            coordinates: self.coordinates(index),
+        })
+    }
+
+    /// Index of the record for `pubkey`, by binary search.
+    fn find(&self, pubkey: &[u8; 32]) -> Option<usize> {
+        let pubkey = &pubkey[..self.key_size];
+        let mut left = 0usize;
+        let mut right = self.len();
+
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let (key, _) = self.record(mid);
+
+            match key.cmp(pubkey) {
+                std::cmp::Ordering::Less => left = mid + 1,
+                std::cmp::Ordering::Greater => right = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+
+        None
+    }
+
+    /// `(stored key, bucket)` in pubkey order. Keys are `key_size()` bytes
+    /// long.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a [u8], u8)> + '_ {
+        (0..self.len()).map(move |index| (self.record(index).0, self.bucket(index)))
+    }
+
+    /// `(bucket, label)` pairs from the v2 label table; empty for v1 maps.
+    /// Entries that are not valid UTF-8 are skipped (see [`Self::validate`]),
+    /// and so is the fields entry.
+    pub fn labels(&self) -> impl Iterator<Item = (u8, &'a str)> + 'a {
+        label_entries(self.labels).filter_map(|(code, label)| {
+            let label = std::str::from_utf8(label).ok()?;
+            (code != FIELDS_ENTRY_CODE).then_some((code, label))
+        })
+    }
+
+    /// Full integrity check: label table, known bucket bytes, strict pubkey
+    /// ordering, optional fields and, for v2 and later, the trailing checksum.
+    pub fn validate(&self) -> Result<(), LeaderGeoMapError> {
+        let mut known_buckets = Vec::new();
+        for (code, label) in label_entries(self.labels) {
+            std::str::from_utf8(label).map_err(|_| LeaderGeoMapError::InvalidLabel { code })?;
+            if code != FIELDS_ENTRY_CODE {
+                known_buckets.push(code);
+            }
+        }
+
+        // Maps with a label table name their buckets; v1 maps use the codes
+        // `geo-rules` knows.
+        for index in 0..self.len() {
+            for bucket in [self.record(index).1, self.bucket(index)] {
+                let known = if known_buckets.is_empty() {
+                    GeoBucket::from_u8(bucket).is_some()
+                } else {
+                    known_buckets.contains(&bucket)
+                };
+                if !known {
+                    return Err(LeaderGeoMapError::UnknownBucket { index, bucket });
+                }
+            }
+        }
+
+        for index in 1..self.len() {
+            if self.record(index - 1).0 >= self.record(index).0 {
+                return Err(LeaderGeoMapError::UnsortedRecords { index });
+            }
+        }
+
+        if self.has_country() {
+            for index in 0..self.len() {
+                let bytes = self.country_bytes(index);
+                if bytes != [0, 0] && !bytes.iter().all(u8::is_ascii_uppercase) {
+                    return Err(LeaderGeoMapError::InvalidCountry(
+                        String::from_utf8_lossy(bytes).into_owned(),
+                    ));
+                }
+            }
+        }
+
+        if self.has_coordinates() {
+            for index in 0..self.len() {
+                if let Some(coordinates) = self.coordinates(index)
+                    && !coordinates.is_valid()
+                {
+                    return Err(LeaderGeoMapError::InvalidCoordinates {
+                        latitude: coordinates.latitude,
+                        longitude: coordinates.longitude,
+                    });
+                }
+            }
+        }
+
+        if self.version != SCHEMA_VERSION_V1 {
+            let (content, checksum) = self.bytes.split_at(self.bytes.len() - CHECKSUM_SIZE);
+            let expected = u64::from_le_bytes(checksum.try_into().expect("8-byte checksum"));
+            let actual = fnv1a64(content);
+            if expected != actual {
+                return Err(LeaderGeoMapError::ChecksumMismatch { expected, actual });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record(&self, index: usize) -> (&'a [u8], u8) {
+        let offset = index * self.record_size;
+        let record = &self.records[offset..offset + self.record_size];
+        (&record[..self.key_size], record[self.key_size])
+    }
+
+    fn bucket(&self, index: usize) -> u8 {
+        match self.fields.bucket {
+            Some(offset) => self.field_bytes(index, offset, BUCKET_SIZE)[0],
+            None => self.record(index).1,
+        }
+    }
+
+    /// `size` bytes of the record at `index`, `field_offset` bytes past the
+    /// bucket byte.
+    fn field_bytes(&self, index: usize, field_offset: usize, size: usize) -> &'a [u8] {
+        let offset = index * self.record_size + self.key_size + field_offset;
+        &self.records[offset..offset + size]
+    }
+
+    fn country_bytes(&self, index: usize) -> &'a [u8] {
+        let offset = self.fields.country.expect("map stores countries");
+        self.field_bytes(index, offset, COUNTRY_SIZE)
+    }
+
+    fn country(&self, index: usize) -> Option<&'a str> {
+        if !self.has_country() {
+            return None;
+        }
+        let bytes = self.country_bytes(index);
+        if !bytes.iter().all(u8::is_ascii_uppercase) {
+            return None;
+        }
+        std::str::from_utf8(bytes).ok()
+    }
+
+    fn coordinates(&self, index: usize) -> Option<Coordinates> {
+        let offset = self.fields.coordinates?;
+        let bytes = self.field_bytes(index, offset, COORDINATES_SIZE);
+        let latitude = i16::from_le_bytes([bytes[0], bytes[1]]);
+        let longitude = i16::from_le_bytes([bytes[2], bytes[3]]);
+        if latitude == NO_COORDINATE || longitude == NO_COORDINATE {
+            return None;
+        }
+        Some(Coordinates::new(
+            f64::from(latitude) / COORDINATE_SCALE,
+            f64::from(longitude) / COORDINATE_SCALE,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BuilderEntry {
+    bucket: GeoBucket,
+    country: Option<[u8; 2]>,
+    coordinates: Option<[i16; 2]>,
+}
+
+/// Collects `pubkey -> bucket` entries and encodes them sorted by pubkey.
+///
+/// Records get the country field as soon as one entry has a country, and the
+/// coordinates field as soon as one entry has coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct LeaderGeoMapBuilder {
+    entries: BTreeMap<[u8; 32], BuilderEntry>,
+}
+
+impl LeaderGeoMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the bucket for `pubkey`.
+    pub fn insert(&mut self, pubkey: [u8; 32], bucket: GeoBucket) -> &mut Self {
+        self.entries.insert(
+            pubkey,
+            BuilderEntry {
+                bucket,
+                country: None,
+                coordinates: None,
+            },
+        );
+        self
+    }
+
+    /// Adds or replaces the bucket and ISO-3166 alpha-2 country for `pubkey`.
+    pub fn insert_with_country(
+        &mut self,
+        pubkey: [u8; 32],
+        bucket: GeoBucket,
+        country: Option<&str>,
+    ) -> Result<&mut Self, LeaderGeoMapError> {
+        self.insert_with_location(pubkey, bucket, country, None)
+    }
+
+    /// Adds or replaces the bucket, country and coordinates for `pubkey`.
+    /// Coordinates are quantized to hundredths of a degree.
+    pub fn insert_with_location(
+        &mut self,
+        pubkey: [u8; 32],
+        bucket: GeoBucket,
+        country: Option<&str>,
+        coordinates: Option<Coordinates>,
+    ) -> Result<&mut Self, LeaderGeoMapError> {
+        let country = country.map(encode_country).transpose()?;
+        let coordinates = coordinates.map(encode_coordinates).transpose()?;
+        self.entries.insert(
+            pubkey,
+            BuilderEntry {
+                bucket,
+                country,
+                coordinates,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Optional fields the records need, in record order. Coordinates always
+    /// come with the country field and the bucket field comes last, which
+    /// keeps the fields at the offsets readers that predate the fields entry
+    /// expect.
+    fn fields(&self) -> Vec<&'static str> {
+        let has_coordinates = self
+            .entries
+            .values()
+            .any(|entry| entry.coordinates.is_some());
+        let has_country = self.entries.values().any(|entry| entry.country.is_some());
+        let has_newer_bucket = self
+            .entries
+            .values()
+            .any(|entry| entry.bucket.legacy_bucket() != entry.bucket);
+
+        let mut fields = Vec::new();
+        if has_country || has_coordinates {
+            fields.push(COUNTRY_FIELD);
+        }
+        if has_coordinates {
+            fields.push(COORDINATES_FIELD);
+        }
+        if has_newer_bucket {
+            fields.push(BUCKET_FIELD);
+        }
+        fields
+    }
+
+    /// Smallest key prefix length that still tells every entry apart.
+    pub fn shortest_unique_prefix_len(&self) -> usize {
+        self.entries
+            .keys()
+            .zip(self.entries.keys().skip(1))
+            .map(|(left, right)| {
+                let common = left.iter().zip(right).take_while(|(a, b)| a == b).count();
+                common + 1
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Encodes the v2 format (full 32-byte keys).
+    pub fn build(&self) -> Result<Vec<u8>, LeaderGeoMapError> {
+        self.encode(SCHEMA_VERSION_V2, PUBKEY_SIZE)
+    }
+
+    /// Encodes the compact v3 format, keeping only the first `key_size`
+    /// bytes of each pubkey. Fails if two entries share that prefix.
+    pub fn build_compact(&self, key_size: usize) -> Result<Vec<u8>, LeaderGeoMapError> {
+        if !(1..=PUBKEY_SIZE).contains(&key_size) {
+            return Err(LeaderGeoMapError::InvalidKeySize(key_size));
+        }
+        let shortest = self.shortest_unique_prefix_len();
+        if key_size < shortest {
+            return Err(LeaderGeoMapError::KeyPrefixCollision { key_size, shortest });
+        }
+        self.encode(SCHEMA_VERSION_V3, key_size)
+    }
+
+    fn encode(&self, version: u16, key_size: usize) -> Result<Vec<u8>, LeaderGeoMapError> {
+        let record_count =
+            u32::try_from(self.entries.len()).map_err(|_| LeaderGeoMapError::TooManyRecords)?;
+
+        let fields = self.fields();
+        let with_country = fields.contains(&COUNTRY_FIELD);
+        let with_coordinates = fields.contains(&COORDINATES_FIELD);
+        let with_bucket = fields.contains(&BUCKET_FIELD);
+        let record_size =
+            key_size + PAYLOAD_SIZE + fields.iter().map(|name| field_size(name)).sum::<usize>();
+
+        let mut output = Vec::with_capacity(64 + self.entries.len() * record_size + CHECKSUM_SIZE);
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&version.to_le_bytes());
+        output.extend_from_slice(&(record_size as u16).to_le_bytes());
+        output.extend_from_slice(&record_count.to_le_bytes());
+        if version >= SCHEMA_VERSION_V3 {
+            output.push(key_size as u8);
+        }
+
+        let fields = fields.join(",");
+        output.push((GeoBucket::ALL.len() + usize::from(!fields.is_empty())) as u8);
+        for bucket in GeoBucket::ALL {
+            let label = bucket.label().as_bytes();
+            output.push(bucket.as_u8());
+            output.push(label.len() as u8);
+            output.extend_from_slice(label);
+        }
+        if !fields.is_empty() {
+            output.push(FIELDS_ENTRY_CODE);
+            output.push(fields.len() as u8);
+            output.extend_from_slice(fields.as_bytes());
+        }
+
+        for (pubkey, entry) in &self.entries {
+            output.extend_from_slice(&pubkey[..key_size]);
+            output.push(entry.bucket.legacy_bucket().as_u8());
+            if with_country {
+                output.extend_from_slice(&entry.country.unwrap_or([0, 0]));
+            }
+            if with_coordinates {
+                let [latitude, longitude] =
+                    entry.coordinates.unwrap_or([NO_COORDINATE, NO_COORDINATE]);
+                output.extend_from_slice(&latitude.to_le_bytes());
+                output.extend_from_slice(&longitude.to_le_bytes());
+            }
+            if with_bucket {
+                output.push(entry.bucket.as_u8());
+            }
+        }
+
+        let checksum = fnv1a64(&output);
+        output.extend_from_slice(&checksum.to_le_bytes());
+        Ok(output)
+    }
+
+    /// Encodes the legacy v1 format: bare 33-byte records, no header and no
+    /// optional fields. Buckets newer than the format are only stored as
+    /// their [`GeoBucket::legacy_bucket`].
+    pub fn build_v1(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.entries.len() * RECORD_SIZE);
+        for (pubkey, entry) in &self.entries {
+            output.extend_from_slice(pubkey);
+            output.push(entry.bucket.legacy_bucket().as_u8());
+        }
+        output
+    }
+}
+
+impl FromIterator<([u8; 32], GeoBucket)> for LeaderGeoMapBuilder {
+    fn from_iter<I: IntoIterator<Item = ([u8; 32], GeoBucket)>>(iter: I) -> Self {
+        let mut builder = Self::new();
+        for (pubkey, bucket) in iter {
+            builder.insert(pubkey, bucket);
+        }
+        builder
+    }
+}
+
+/// Record bytes taken by a known optional field.
+fn field_size(name: &str) -> usize {
+    match name {
+        COUNTRY_FIELD => COUNTRY_SIZE,
+        COORDINATES_FIELD => COORDINATES_SIZE,
+        BUCKET_FIELD => BUCKET_SIZE,
+        _ => 0,
+    }
+}
+
+/// `(code, label bytes)` entries of a label table.
+fn label_entries(mut labels: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let [code, len, tail @ ..] = labels else {
+            return None;
+        };
+        let (label, next) = tail.split_at(usize::from(*len));
+        labels = next;
+        Some((*code, label))
+    })
+}
+
+fn encode_country(country: &str) -> Result<[u8; 2], LeaderGeoMapError> {
+    let upper = country.trim().to_ascii_uppercase();
+    match upper.as_bytes() {
+        [a, b] if a.is_ascii_uppercase() && b.is_ascii_uppercase() => Ok([*a, *b]),
+        _ => Err(LeaderGeoMapError::InvalidCountry(country.to_string())),
+    }
+}
+
+fn encode_coordinates(coordinates: Coordinates) -> Result<[i16; 2], LeaderGeoMapError> {
+    if !coordinates.is_valid() {
+        return Err(LeaderGeoMapError::InvalidCoordinates {
+            latitude: coordinates.latitude,
+            longitude: coordinates.longitude,
+        });
+    }
+    // In range after the check above: |value| * 100 <= 18_000.
+    let quantize = |value: f64| (value * COORDINATE_SCALE).round() as i16;
+    Ok([
+        quantize(coordinates.latitude),
+        quantize(coordinates.longitude),
+    ])
+}
+
+/// 64-bit FNV-1a, the map checksum. Exported so other crates hashing
+/// pubkeys don't carry their own copy.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> LeaderGeoMapBuilder {
+        [
+            ([3u8; 32], GeoBucket::Apac),
+            ([1u8; 32], GeoBucket::Eu),
+            ([2u8; 32], GeoBucket::Na),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn v2_round_trips_through_builder_and_view() {
+        let bytes = builder().build().unwrap();
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+        assert_eq!(map.version(), SCHEMA_VERSION_V2);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&[2u8; 32]), Some(GeoBucket::Na.as_u8()));
+        assert_eq!(map.get(&[9u8; 32]), None);
+        assert_eq!(
+            map.iter().map(|(key, _)| key[0]).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            map.labels().collect::<Vec<_>>(),
+            GeoBucket::ALL
+                .iter()
+                .map(|bucket| (bucket.as_u8(), bucket.label()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn v1_maps_are_still_readable() {
+        let bytes = builder().build_v1();
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+        assert_eq!(map.version(), SCHEMA_VERSION_V1);
+        assert_eq!(map.get(&[3u8; 32]), Some(GeoBucket::Apac.as_u8()));
+        assert_eq!(map.labels().count(), 0);
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn newer_buckets_store_their_legacy_bucket_in_the_bucket_byte() {
+        let mut builder = builder();
+        builder.insert([4u8; 32], GeoBucket::Latam);
+        builder
+            .insert_with_country([5u8; 32], GeoBucket::Oceania, Some("AU"))
+            .unwrap();
+
+        for bytes in [
+            builder.build().unwrap(),
+            builder.build_compact(DEFAULT_KEY_PREFIX_SIZE).unwrap(),
+        ] {
+            let map = LeaderGeoMap::parse(&bytes).unwrap();
+            assert_eq!(map.validate(), Ok(()));
+
+            // Readers that predate LATAM and OCEANIA route on the byte right
+            // after the key, and find the country right after that.
+            for (key, legacy, bucket) in [
+                ([4u8; 32], GeoBucket::Na, GeoBucket::Latam),
+                ([5u8; 32], GeoBucket::Apac, GeoBucket::Oceania),
+                ([1u8; 32], GeoBucket::Eu, GeoBucket::Eu),
+            ] {
+                let record = map.find(&key).unwrap() * map.record_size();
+                let bucket_byte = record + map.key_size();
+                assert_eq!(map.records[bucket_byte], legacy.as_u8());
+                assert_eq!(map.get(&key), Some(bucket.as_u8()));
+            }
+            let record = map.find(&[5u8; 32]).unwrap() * map.record_size();
+            let country = record + map.key_size() + PAYLOAD_SIZE;
+            assert_eq!(&map.records[country..country + COUNTRY_SIZE], b"AU");
+        }
+
+        let map_v1 = builder.build_v1();
+        let map = LeaderGeoMap::parse(&map_v1).unwrap();
+        assert_eq!(map.get(&[4u8; 32]), Some(GeoBucket::Na.as_u8()));
+        assert_eq!(map.get(&[5u8; 32]), Some(GeoBucket::Apac.as_u8()));
+
+        // Maps without newer buckets don't carry the field.
+        let bytes = self::builder().build().unwrap();
+        assert_eq!(
+            LeaderGeoMap::parse(&bytes).unwrap().record_size(),
+            RECORD_SIZE
+        );
+    }
+
+    #[test]
+    fn country_records_round_trip() {
+        let mut builder = builder();
+        builder
+            .insert_with_country([4u8; 32], GeoBucket::Unknown, Some("br"))
+            .unwrap()
+            .insert_with_country([5u8; 32], GeoBucket::Eu, None)
+            .unwrap();
+        let bytes = builder.build().unwrap();
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+        assert!(map.has_country());
+        assert_eq!(map.record_size(), RECORD_SIZE_WITH_COUNTRY);
+        assert_eq!(
+            map.get_record(&[4u8; 32]),
+            Some(LeaderGeoRecord {
+                bucket: GeoBucket::Unknown.as_u8(),
+                country: Some("BR"),
+                coordinates: None,
+            })
+        );
+        assert_eq!(map.get_record(&[5u8; 32]).unwrap().country, None);
+        assert_eq!(map.get(&[4u8; 32]), Some(GeoBucket::Unknown.as_u8()));
+        assert_eq!(map.labels().count(), GeoBucket::ALL.len());
+        assert_eq!(map.validate(), Ok(()));
+
+        assert!(
+            LeaderGeoMapBuilder::new()
+                .insert_with_country([1u8; 32], GeoBucket::Eu, Some("DEU"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn coordinate_records_round_trip() {
+        let mut builder = builder();
+        builder
+            .insert_with_location(
+                [4u8; 32],
+                GeoBucket::Me,
+                Some("TR"),
+                Some(Coordinates::new(41.0138, 28.9497)),
+            )
+            .unwrap();
+        let bytes = builder.build().unwrap();
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+        assert!(map.has_country() && map.has_coordinates());
+        assert_eq!(map.record_size(), RECORD_SIZE_WITH_COORDINATES);
+        assert_eq!(
+            map.get_record(&[4u8; 32]),
+            Some(LeaderGeoRecord {
+                bucket: GeoBucket::Me.as_u8(),
+                country: Some("TR"),
+                coordinates: Some(Coordinates::new(41.01, 28.95)),
+            })
+        );
+        let untouched = map.get_record(&[1u8; 32]).unwrap();
+        assert_eq!((untouched.country, untouched.coordinates), (None, None));
+        assert_eq!(map.validate(), Ok(()));
+
+        assert!(
+            LeaderGeoMapBuilder::new()
+                .insert_with_location(
+                    [1u8; 32],
+                    GeoBucket::Eu,
+                    None,
+                    Some(Coordinates::new(91.0, 0.0))
+                )
+                .is_err()
+        );
+    }
+
+    /// A v2 map with one EU record followed by `extension` bytes and the
+    /// given fields entry, encoded by hand like a future writer would.
+    fn v2_map_with_extension(extension: &[u8], fields: Option<&str>) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&SCHEMA_VERSION_V2.to_le_bytes());
+        bytes.extend_from_slice(&((RECORD_SIZE + extension.len()) as u16).to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(1 + u8::from(fields.is_some()));
+        bytes.extend_from_slice(&[GeoBucket::Eu.as_u8(), 2, b'E', b'U']);
+        if let Some(fields) = fields {
+            bytes.extend_from_slice(&[FIELDS_ENTRY_CODE, fields.len() as u8]);
+            bytes.extend_from_slice(fields.as_bytes());
+        }
+        bytes.extend_from_slice(&[7u8; 32]);
+        bytes.push(GeoBucket::Eu.as_u8());
+        bytes.extend_from_slice(extension);
+        let checksum = fnv1a64(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn extra_record_bytes_are_opaque_without_fields_entry() {
+        for fields in [None, Some("altitude,country")] {
+            let bytes = v2_map_with_extension(b"DE\x01\x02\x03\x04\x05", fields);
+            let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+            assert!(!map.has_country() && !map.has_coordinates());
+            assert_eq!(
+                map.get_record(&[7u8; 32]),
+                Some(LeaderGeoRecord {
+                    bucket: GeoBucket::Eu.as_u8(),
+                    country: None,
+                    coordinates: None,
+                })
+            );
+            assert_eq!(map.labels().collect::<Vec<_>>(), vec![(1, "EU")]);
+            assert_eq!(map.validate(), Ok(()));
+        }
+
+        let bytes = v2_map_with_extension(b"DE\x01", Some("country"));
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+        assert_eq!(map.get_record(&[7u8; 32]).unwrap().country, Some("DE"));
+
+        assert_eq!(
+            LeaderGeoMap::parse(&v2_map_with_extension(b"DE", Some("country,coordinates")))
+                .unwrap_err(),
+            LeaderGeoMapError::RecordSizeTooSmall(RECORD_SIZE + 2)
+        );
+    }
+
+    #[test]
+    fn compact_maps_store_key_prefixes() {
+        let mut builder = builder();
+        let mut near_miss = [1u8; 32];
+        near_miss[2] = 9;
+        builder
+            .insert_with_country(near_miss, GeoBucket::Me, Some("AE"))
+            .unwrap();
+        assert_eq!(builder.shortest_unique_prefix_len(), 3);
+
+        let bytes = builder.build_compact(DEFAULT_KEY_PREFIX_SIZE).unwrap();
+        let map = LeaderGeoMap::parse(&bytes).unwrap();
+
+        assert_eq!(map.version(), SCHEMA_VERSION_V3);
+        assert_eq!(map.key_size(), DEFAULT_KEY_PREFIX_SIZE);
+        assert_eq!(map.record_size(), DEFAULT_KEY_PREFIX_SIZE + 3);
+        assert_eq!(map.get(&[2u8; 32]), Some(GeoBucket::Na.as_u8()));
+        assert_eq!(map.get_record(&near_miss).unwrap().country, Some("AE"));
+        assert_eq!(map.get(&[9u8; 32]), None);
+        assert_eq!(map.validate(), Ok(()));
+        assert!(bytes.len() < builder.build().unwrap().len());
+
+        assert_eq!(
+            builder.build_compact(2).unwrap_err(),
+            LeaderGeoMapError::KeyPrefixCollision {
+                key_size: 2,
+                shortest: 3
+            }
+        );
+        assert_eq!(
+            builder.build_compact(33).unwrap_err(),
+            LeaderGeoMapError::InvalidKeySize(33)
+        );
+    }
+
+    #[test]
+    fn unknown_schema_versions_are_rejected() {
+        let mut builder = builder();
+        builder.insert([4u8; 32], GeoBucket::Eu);
+        let mut bytes = builder.build_compact(DEFAULT_KEY_PREFIX_SIZE).unwrap();
+        bytes[4..6].copy_from_slice(&4u16.to_le_bytes());
+
+        assert_eq!(
+            LeaderGeoMap::parse(&bytes).unwrap_err(),
+            LeaderGeoMapError::UnsupportedVersion(4)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_misaligned_and_truncated_maps() {
+        assert_eq!(
+            LeaderGeoMap::parse(&[1, 2, 3]).unwrap_err(),
+            LeaderGeoMapError::Misaligned { len: 3 }
+        );
+
+        let bytes = builder().build().unwrap();
+        assert!(matches!(
+            LeaderGeoMap::parse(&bytes[..bytes.len() - 1]),
+            Err(LeaderGeoMapError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn validate_detects_corruption() {
+        let mut bytes = builder().build().unwrap();
+        let first_record = bytes.len() - CHECKSUM_SIZE - 3 * RECORD_SIZE;
+        bytes[first_record + PUBKEY_SIZE] = GeoBucket::Me.as_u8();
+        assert!(matches!(
+            LeaderGeoMap::parse(&bytes).unwrap().validate(),
+            Err(LeaderGeoMapError::ChecksumMismatch { .. })
+        ));
+
+        let mut unsorted = builder().build_v1();
+        unsorted[..PUBKEY_SIZE].copy_from_slice(&[5u8; 32]);
+        assert_eq!(
+            LeaderGeoMap::parse(&unsorted).unwrap().validate(),
+            Err(LeaderGeoMapError::UnsortedRecords { index: 1 })
+        );
+
+        let mut unknown_bucket = builder().build_v1();
+        unknown_bucket[RECORD_SIZE + PUBKEY_SIZE] = 200;
+        assert_eq!(
+            LeaderGeoMap::parse(&unknown_bucket).unwrap().validate(),
+            Err(LeaderGeoMapError::UnknownBucket {
+                index: 1,
+                bucket: 200
+            })
+        );
+    }
+}
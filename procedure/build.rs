@@ -0,0 +1,160 @@
+//! Fails the build when `data/leader_geo_map.bin` doesn't match its
+//! `leader_geo_map.meta.json` sidecar or the rules embedded in `geo-rules`
+//! are invalid, and exposes the sidecar as constants
+//! (`procedure::map_metadata`). The `BUCKET_COUNTS` the hash fallback is
+//! weighted by are counted from the map itself.
+
+use std::{env, error::Error, fs, path::PathBuf};
+
+use geo_rules::{EMBEDDED_RULES_JSON, GeoBucket, RuleSet};
+use leader_geo_map::{LeaderGeoMap, SCHEMA_VERSION_V1, SCHEMA_VERSION_V3};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const MAP_PATH: &str = "data/leader_geo_map.bin";
+const META_PATH: &str = "data/leader_geo_map.meta.json";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed={MAP_PATH}");
+    println!("cargo:rerun-if-changed={META_PATH}");
+
+    RuleSet::from_json(EMBEDDED_RULES_JSON)
+        .map_err(|err| format!("geo-rules data/rules.json: {err}"))?;
+
+    let map_bytes = fs::read(MAP_PATH)?;
+    let meta: Value = serde_json::from_str(&fs::read_to_string(META_PATH)?)?;
+
+    let schema_version = meta_u64(&meta, "schema_version")?;
+    if !(u64::from(SCHEMA_VERSION_V1)..=u64::from(SCHEMA_VERSION_V3)).contains(&schema_version) {
+        return Err(format!("{META_PATH}: unsupported schema_version {schema_version}").into());
+    }
+
+    let map_sha256 = meta_str(&meta, "map_sha256")?;
+    let actual_sha256 = format!("{:x}", Sha256::digest(&map_bytes));
+    if actual_sha256 != map_sha256 {
+        return Err(format!(
+            "{MAP_PATH} has sha256 {actual_sha256} but {META_PATH} says {map_sha256}; \
+             regenerate both with scripts/rebuild-leader-geo-map.sh"
+        )
+        .into());
+    }
+
+    let map = LeaderGeoMap::parse(&map_bytes).map_err(|err| format!("{MAP_PATH}: {err}"))?;
+    map.validate().map_err(|err| format!("{MAP_PATH}: {err}"))?;
+    if u64::from(map.version()) != schema_version {
+        return Err(format!(
+            "{MAP_PATH} is schema version {} but {META_PATH} says {schema_version}",
+            map.version()
+        )
+        .into());
+    }
+    for (field, actual) in [
+        ("record_size_bytes", map.record_size()),
+        ("map_size_bytes", map_bytes.len()),
+        ("total_leaders", map.len()),
+    ] {
+        let expected = meta_u64(&meta, field)?;
+        if expected != actual as u64 {
+            return Err(
+                format!("{MAP_PATH} has {field} {actual} but {META_PATH} says {expected}").into(),
+            );
+        }
+    }
+
+    let bucket_counts = map_bucket_counts(&map)?;
+    if meta_bucket_counts(&meta)? != bucket_counts {
+        return Err(format!(
+            "{META_PATH}: bucket_counts disagree with {MAP_PATH}, which has {bucket_counts:?}"
+        )
+        .into());
+    }
+    let mapped_leaders = meta_u64(&meta, "mapped_leaders")?;
+    let counted_leaders: u64 = bucket_counts.iter().map(|(_, count)| count).sum();
+    if counted_leaders != mapped_leaders {
+        return Err(format!(
+            "{MAP_PATH} has {counted_leaders} mapped leaders but {META_PATH} says {mapped_leaders}"
+        )
+        .into());
+    }
+
+    let mut constants = format!(
+        "pub const SCHEMA_VERSION: u16 = {schema_version};\n\
+         pub const GENERATED_AT_UNIX_SECS: u64 = {};\n\
+         pub const RPC_SLOT: u64 = {};\n\
+         pub const MAP_SHA256: &str = {map_sha256:?};\n\
+         pub const TOTAL_LEADERS: usize = {};\n\
+         pub const MAPPED_LEADERS: usize = {};\n",
+        meta_u64(&meta, "generated_at_unix_secs")?,
+        meta_u64(&meta, "rpc_slot")?,
+        map.len(),
+        mapped_leaders,
+    );
+    constants.push_str(&format!(
+        "pub const BUCKET_COUNTS: [(geo_rules::GeoBucket, u64); {}] = [\n",
+        bucket_counts.len()
+    ));
+    for (bucket, count) in bucket_counts {
+        constants.push_str(&format!(
+            "    (geo_rules::GeoBucket::{bucket:?}, {count}),\n"
+        ));
+    }
+    constants.push_str("];\n");
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    fs::write(out_dir.join("leader_geo_map_meta.rs"), constants)?;
+
+    Ok(())
+}
+
+fn meta_u64(meta: &Value, field: &str) -> Result<u64, String> {
+    meta.get(field)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| format!("{META_PATH}: missing or non-integer {field}"))
+}
+
+fn meta_str<'a>(meta: &'a Value, field: &str) -> Result<&'a str, String> {
+    meta.get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("{META_PATH}: missing or non-string {field}"))
+}
+
+/// Leaders per bucket recorded in the map, `UNKNOWN` excluded, in bucket
+/// code order.
+fn map_bucket_counts(map: &LeaderGeoMap) -> Result<Vec<(GeoBucket, u64)>, String> {
+    let mut counts = vec![0u64; GeoBucket::ALL.len()];
+    for (_, bucket) in map.iter() {
+        let count = counts
+            .get_mut(usize::from(bucket))
+            .ok_or_else(|| format!("{MAP_PATH}: unknown bucket code {bucket}"))?;
+        *count += 1;
+    }
+    Ok(GeoBucket::ALL
+        .into_iter()
+        .zip(counts)
+        .filter(|(bucket, count)| *bucket != GeoBucket::Unknown && *count > 0)
+        .collect())
+}
+
+/// The sidecar's `bucket_counts` as `(bucket, leaders)` in bucket code
+/// order, rejecting unknown labels and non-integer counts.
+fn meta_bucket_counts(meta: &Value) -> Result<Vec<(GeoBucket, u64)>, String> {
+    let counts = meta
+        .get("bucket_counts")
+        .and_then(Value::as_object)
+        .ok_or_else(|| format!("{META_PATH}: missing or non-object bucket_counts"))?;
+    counts
+        .iter()
+        .map(|(label, count)| {
+            let bucket = GeoBucket::from_label(label)
+                .ok_or_else(|| format!("{META_PATH}: unknown bucket {label:?} in bucket_counts"))?;
+            let count = count
+                .as_u64()
+                .ok_or_else(|| format!("{META_PATH}: non-integer bucket_counts.{label}"))?;
+            Ok((bucket, count))
+        })
+        .filter(|entry| !matches!(entry, Ok((_, 0))))
+        .collect::<Result<Vec<_>, String>>()
+        .map(|mut counts| {
+            counts.sort_by_key(|(bucket, _)| bucket.as_u8());
+            counts
+        })
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use geo_rules::{GeoBucket, region_from_bucket};
+use leader_geo_map::fnv1a64;
+
+use crate::{ALL_REGIONS, Region, decode_leader_pubkey, map_metadata};
+
+/// How many mapped leaders each region serves, taken from the map's
+/// `bucket_counts`. Unknown leaders are hashed onto regions in proportion to
+/// these counts instead of uniformly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FallbackWeights {
+    leaders_per_region: HashMap<Region, u64>,
+}
+
+impl FallbackWeights {
+    /// Sums per-bucket leader counts into per-region weights. Buckets without
+    /// a region (`Unknown`) don't count.
+    pub(crate) fn from_bucket_counts(bucket_counts: &[(GeoBucket, u64)]) -> Self {
+        let mut leaders_per_region = HashMap::new();
+        for (bucket, count) in bucket_counts {
+            if let Some(region) = region_from_bucket(*bucket) {
+                *leaders_per_region.entry(region).or_insert(0) += count;
+            }
+        }
+        Self { leaders_per_region }
+    }
+
+    /// Weight of every region in `regions`. Falls back to equal weights when
+    /// none of them has any mapped leaders.
+    fn weights_for(&self, regions: &[Region]) -> Vec<(Region, f64)> {
+        let weights: Vec<(Region, f64)> = regions
+            .iter()
+            .map(|region| {
+                let count = self.leaders_per_region.get(region).copied().unwrap_or(0);
+                (*region, count as f64)
+            })
+            .collect();
+
+        if weights.iter().any(|(_, weight)| *weight > 0.0) {
+            weights
+        } else {
+            regions.iter().map(|region| (*region, 1.0)).collect()
+        }
+    }
+}
+
+fn embedded_fallback_weights() -> &'static FallbackWeights {
+    static WEIGHTS: OnceLock<FallbackWeights> = OnceLock::new();
+    WEIGHTS.get_or_init(|| FallbackWeights::from_bucket_counts(&map_metadata::BUCKET_COUNTS))
+}
+
+/// Weighted rendezvous (highest random weight) hash of the leader over the
+/// available regions, weighted by the embedded map's bucket distribution.
+/// Adding or removing a region only moves the leaders that win or lose that
+/// region; every other leader keeps its region.
+pub(crate) fn fallback_region(leader_pubkey: &str, regions: &[Region]) -> Region {
+    regions_by_rendezvous_weight(leader_pubkey, regions)[0]
+}
+
+/// `regions` ordered by weighted rendezvous score for the leader, best first.
+pub(crate) fn regions_by_rendezvous_weight(leader_pubkey: &str, regions: &[Region]) -> Vec<Region> {
+    weighted_rendezvous_order(leader_pubkey, regions, embedded_fallback_weights())
+}
+
+/// The hash runs over the decoded 32-byte key; strings that are not valid
+/// pubkeys are hashed as-is so they still route deterministically.
+fn weighted_rendezvous_order(
+    leader_pubkey: &str,
+    regions: &[Region],
+    weights: &FallbackWeights,
+) -> Vec<Region> {
+    let decoded = decode_leader_pubkey(leader_pubkey);
+    let key = decoded
+        .as_ref()
+        .map_or(leader_pubkey.as_bytes(), |pubkey| pubkey.as_slice());
+
+    let regions = if regions.is_empty() {
+        &ALL_REGIONS[..]
+    } else {
+        regions
+    };
+
+    let mut scored: Vec<(Region, f64)> = weights
+        .weights_for(regions)
+        .into_iter()
+        .map(|(region, weight)| (region, rendezvous_score(key, region, weight)))
+        .collect();
+    scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    scored.into_iter().map(|(region, _)| region).collect()
+}
+
+/// `weight / -ln(u)` with `u` uniform in (0, 1) from the key/region hash. The
+/// probability that a region has the highest score is proportional to its
+/// weight.
+fn rendezvous_score(key: &[u8], region: Region, weight: f64) -> f64 {
+    let mut bytes = Vec::with_capacity(key.len() + 16);
+    bytes.extend_from_slice(key);
+    bytes.extend_from_slice(region.name().as_bytes());
+    let hash = fmix64(fnv1a64(&bytes));
+
+    let unit = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    weight / -unit.ln()
+}
+
+/// MurmurHash3 finalizer. FNV-1a barely mixes its last input bytes, and the
+/// region name is always the suffix, so the scores need an avalanche step.
+fn fmix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthetic_leaders(count: u32) -> Vec<String> {
+        (0..count)
+            .map(|index| {
+                let mut key = [7u8; 32];
+                key[..4].copy_from_slice(&index.to_le_bytes());
+                bs58::encode(key).into_string()
+            })
+            .collect()
+    }
+
+    fn share_per_region(weights: &FallbackWeights, leaders: &[String]) -> Vec<(Region, usize)> {
+        ALL_REGIONS
+            .into_iter()
+            .map(|region| {
+                let assigned = leaders
+                    .iter()
+                    .filter(|leader| {
+                        weighted_rendezvous_order(leader, &ALL_REGIONS, weights)[0] == region
+                    })
+                    .count();
+                (region, assigned * 100 / leaders.len())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fallback_only_moves_leaders_to_an_added_region() {
+        let without_tokyo = [Region::Dubai, Region::Frankfurt, Region::NewYork];
+        let weights = FallbackWeights::default();
+        let leaders = synthetic_leaders(4_000);
+
+        let mut moved = 0;
+        for leader in &leaders {
+            let before = weighted_rendezvous_order(leader, &without_tokyo, &weights)[0];
+            let after = weighted_rendezvous_order(leader, &ALL_REGIONS, &weights)[0];
+            if before != after {
+                assert_eq!(after, Region::Tokyo);
+                moved += 1;
+            }
+        }
+
+        // Ideal is 1/4 of the leaders; `fnv1a64 % n` would move about 3/4.
+        let moved_pct = moved * 100 / leaders.len();
+        assert!((20..=30).contains(&moved_pct), "moved {moved_pct}%");
+    }
+
+    #[test]
+    fn fallback_without_bucket_counts_is_uniform() {
+        let leaders = synthetic_leaders(4_000);
+
+        for (region, pct) in share_per_region(&FallbackWeights::default(), &leaders) {
+            assert!((20..=30).contains(&pct), "{region:?}: {pct}%");
+        }
+    }
+
+    #[test]
+    fn fallback_follows_bucket_distribution() {
+        let weights = FallbackWeights::from_bucket_counts(&[
+            (GeoBucket::Eu, 60),
+            (GeoBucket::Na, 20),
+            (GeoBucket::Apac, 20),
+            (GeoBucket::Me, 0),
+        ]);
+        let leaders = synthetic_leaders(4_000);
+
+        let shares = share_per_region(&weights, &leaders);
+
+        assert_eq!(shares[0], (Region::Dubai, 0));
+        assert!((55..=65).contains(&shares[1].1), "{shares:?}");
+        assert!((15..=25).contains(&shares[2].1), "{shares:?}");
+        assert!((15..=25).contains(&shares[3].1), "{shares:?}");
+    }
+
+    #[test]
+    fn fallback_uses_zero_weight_regions_when_nothing_else_is_available() {
+        let weights = FallbackWeights::from_bucket_counts(&[(GeoBucket::Eu, 10)]);
+
+        assert_eq!(
+            weighted_rendezvous_order("validator-x", &[Region::Dubai], &weights),
+            vec![Region::Dubai]
+        );
+    }
+
+    #[test]
+    fn embedded_metadata_provides_weights_for_every_region() {
+        let weights = embedded_fallback_weights();
+        for region in ALL_REGIONS {
+            assert!(
+                weights
+                    .leaders_per_region
+                    .get(&region)
+                    .copied()
+                    .unwrap_or(0)
+                    > 0
+            );
+        }
+    }
+}
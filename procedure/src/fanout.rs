@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+use crate::Region;
+use crate::leader_windows::LeaderWindow;
+
+/// Inclusive slot range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SlotRange {
+    pub start_slot: u64,
+    pub end_slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegionFanout {
+    pub region: Region,
+    pub slot_ranges: Vec<SlotRange>,
+}
+
+/// Regions a transaction should be submitted to so that every leader between
+/// `start_slot` and `end_slot` is reached from its closest region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FanoutPlan {
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub regions: Vec<RegionFanout>,
+}
+
+/// Groups leader windows by `closest_region`.
+///
+/// Each leader routes to exactly one region, so the distinct regions of the
+/// windows are already the smallest covering set. Regions are ordered by the
+/// first slot they cover, and adjacent ranges in the same region are merged.
+pub(crate) fn plan_region_fanout(windows: &[LeaderWindow]) -> Option<FanoutPlan> {
+    let start_slot = windows.first()?.start_slot;
+    let end_slot = windows.last()?.end_slot;
+    let mut regions: Vec<RegionFanout> = Vec::new();
+
+    for window in windows {
+        let range = SlotRange {
+            start_slot: window.start_slot,
+            end_slot: window.end_slot,
+        };
+
+        let Some(fanout) = regions
+            .iter_mut()
+            .find(|fanout| fanout.region == window.closest_region)
+        else {
+            regions.push(RegionFanout {
+                region: window.closest_region,
+                slot_ranges: vec![range],
+            });
+            continue;
+        };
+
+        match fanout.slot_ranges.last_mut() {
+            Some(last) if last.end_slot + 1 == range.start_slot => last.end_slot = range.end_slot,
+            _ => fanout.slot_ranges.push(range),
+        }
+    }
+
+    Some(FanoutPlan {
+        start_slot,
+        end_slot,
+        regions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start_slot: u64, end_slot: u64, closest_region: Region) -> LeaderWindow {
+        LeaderWindow {
+            start_slot,
+            end_slot,
+            leader: format!("leader-{start_slot}"),
+            leader_geo: "EU".to_string(),
+            closest_region,
+        }
+    }
+
+    #[test]
+    fn plan_region_fanout_covers_every_window_with_distinct_regions() {
+        let windows = [
+            window(10, 11, Region::Frankfurt),
+            window(12, 15, Region::Frankfurt),
+            window(16, 19, Region::NewYork),
+            window(20, 23, Region::Frankfurt),
+        ];
+
+        let plan = plan_region_fanout(&windows).unwrap();
+
+        assert_eq!((plan.start_slot, plan.end_slot), (10, 23));
+        assert_eq!(
+            plan.regions,
+            vec![
+                RegionFanout {
+                    region: Region::Frankfurt,
+                    slot_ranges: vec![
+                        SlotRange {
+                            start_slot: 10,
+                            end_slot: 15
+                        },
+                        SlotRange {
+                            start_slot: 20,
+                            end_slot: 23
+                        },
+                    ],
+                },
+                RegionFanout {
+                    region: Region::NewYork,
+                    slot_ranges: vec![SlotRange {
+                        start_slot: 16,
+                        end_slot: 19
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_region_fanout_is_none_without_windows() {
+        assert_eq!(plan_region_fanout(&[]), None);
+    }
+}
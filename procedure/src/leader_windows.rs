@@ -0,0 +1,123 @@
+use serde::Serialize;
+
+use crate::{Region, derive_leader_geo_and_region};
+
+/// Consecutive slots a leader holds per rotation on mainnet.
+const LEADER_WINDOW_SLOTS: u64 = 4;
+
+/// A run of consecutive slots led by the same validator, together with where
+/// to route transactions for it. `end_slot` is inclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LeaderWindow {
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub leader: String,
+    pub leader_geo: String,
+    pub closest_region: Region,
+}
+
+/// Collapses per-slot leaders (as returned by `getSlotLeaders`) into leader
+/// windows. Mainnet leaders hold 4 consecutive slots, so a full window covers
+/// 4 slots and a leader scheduled for back-to-back rotations gets one window
+/// per rotation; the first and last windows may be cut short by `start_slot`
+/// and the end of `slot_leaders`. Geo and region are derived once per leader
+/// run.
+pub(crate) fn collapse_leader_windows<S: AsRef<str>>(
+    start_slot: u64,
+    slot_leaders: &[S],
+    geo_map: &[u8],
+    regions: &[Region],
+) -> Vec<LeaderWindow> {
+    let mut windows: Vec<LeaderWindow> = Vec::new();
+
+    for (slot, leader) in (start_slot..).zip(slot_leaders) {
+        let leader = leader.as_ref();
+        if let Some(window) = windows.last_mut()
+            && window.leader == leader
+            && slot % LEADER_WINDOW_SLOTS != 0
+        {
+            window.end_slot = slot;
+            continue;
+        }
+
+        let previous = windows.last().filter(|window| window.leader == leader);
+        let (leader_geo, closest_region) = match previous {
+            Some(window) => (window.leader_geo.clone(), window.closest_region),
+            None => {
+                let (leader_geo, _, closest_region) =
+                    derive_leader_geo_and_region(leader, geo_map, regions);
+                (leader_geo, closest_region)
+            }
+        };
+        windows.push(LeaderWindow {
+            start_slot: slot,
+            end_slot: slot,
+            leader: leader.to_string(),
+            leader_geo,
+            closest_region,
+        });
+    }
+
+    windows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ALL_REGIONS, UNKNOWN_GEO, fallback_region};
+
+    fn leaders(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn collapse_leader_windows_groups_consecutive_slots() {
+        let slot_leaders = leaders(&["a", "a", "b", "b", "b", "b", "c"]);
+
+        let windows = collapse_leader_windows(102, &slot_leaders, &[], &ALL_REGIONS);
+
+        let spans: Vec<(u64, u64, &str)> = windows
+            .iter()
+            .map(|window| (window.start_slot, window.end_slot, window.leader.as_str()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(102, 103, "a"), (104, 107, "b"), (108, 108, "c")]
+        );
+    }
+
+    #[test]
+    fn collapse_leader_windows_splits_back_to_back_rotations() {
+        let slot_leaders = leaders(&["a"; 8]);
+
+        let spans: Vec<(u64, u64)> = collapse_leader_windows(100, &slot_leaders, &[], &ALL_REGIONS)
+            .iter()
+            .map(|window| (window.start_slot, window.end_slot))
+            .collect();
+        assert_eq!(spans, vec![(100, 103), (104, 107)]);
+
+        // Starting mid-rotation cuts the first window short.
+        let spans: Vec<(u64, u64)> = collapse_leader_windows(102, &slot_leaders, &[], &ALL_REGIONS)
+            .iter()
+            .map(|window| (window.start_slot, window.end_slot))
+            .collect();
+        assert_eq!(spans, vec![(102, 103), (104, 107), (108, 109)]);
+    }
+
+    #[test]
+    fn collapse_leader_windows_derives_geo_per_window() {
+        let windows = collapse_leader_windows(0, &leaders(&["a", "a"]), &[], &ALL_REGIONS);
+
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].leader_geo, UNKNOWN_GEO);
+        assert_eq!(
+            windows[0].closest_region,
+            fallback_region("a", &ALL_REGIONS)
+        );
+    }
+
+    #[test]
+    fn collapse_leader_windows_handles_empty_input() {
+        assert!(collapse_leader_windows::<String>(0, &[], &[], &ALL_REGIONS).is_empty());
+    }
+}
@@ -1,14 +1,47 @@
-use geo_rules::{GeoBucket, Region, region_from_geo_input};
-use log::info;
+mod fallback;
+mod fanout;
+mod leader_windows;
+mod map_provenance;
+mod params;
+mod route_table;
+mod validator_schedule;
+
+/// Metadata of the embedded map, from `data/leader_geo_map.meta.json`. The
+/// build script checks the sidecar against the map before exposing it.
+pub mod map_metadata {
+    include!(concat!(env!("OUT_DIR"), "/leader_geo_map_meta.rs"));
+}
+
+use fallback::{fallback_region, regions_by_rendezvous_weight};
+use fanout::plan_region_fanout;
+use geo_rules::{
+    ALL_REGIONS, Coordinates, GeoBucket, bucket_from_country_iso, bucket_from_geo_input,
+    estimated_rtt_ms, region_coordinates, region_from_geo_input, region_override_for_country,
+    regions_ranked_by_distance, select_regions,
+};
+use leader_geo_map::{LeaderGeoMap, LeaderGeoMapError, LeaderGeoRecord};
+use leader_windows::collapse_leader_windows;
+use log::{error, info, warn};
+use map_provenance::DEFAULT_MAX_MAP_AGE_SLOTS;
+use route_table::{fetch_epoch_route_table, invert_leader_schedule};
 use serde::Serialize;
+use std::sync::OnceLock;
+use validator_schedule::fetch_validator_schedule;
 use zela_std::rpc_client::{RpcClient, response::RpcLeaderSchedule};
-use zela_std::{CustomProcedure, JsonValue, RpcError};
+use zela_std::{CustomProcedure, RpcError};
 
+pub use fanout::{FanoutPlan, RegionFanout, SlotRange};
+pub use geo_rules::Region;
+pub use leader_windows::LeaderWindow;
+pub use map_provenance::MapProvenance;
+pub use params::LeaderRoutingParams;
+pub use route_table::EpochRouteTable;
+pub use validator_schedule::ValidatorSchedule;
+
+const ERROR_CODE_INVALID_PARAMS: i32 = 400;
 const ERROR_CODE_INTERNAL: i32 = 500;
 const UNKNOWN_GEO: &str = "UNKNOWN";
 const LEADER_GEO_MAP_BIN: &[u8] = include_bytes!("../data/leader_geo_map.bin");
-const LEADER_GEO_RECORD_SIZE: usize = 33;
-
 pub struct LeaderRoutingProcedure;
 
 #[derive(Debug, Clone, Serialize)]
@@ -16,30 +49,67 @@ pub struct LeaderRoutingOutput {
     pub slot: u64,
     pub leader: String,
     pub leader_geo: String,
-    pub closest_region: ServerRegion,
+    pub geo_source: GeoSource,
+    /// ISO-3166 alpha-2 country, only when the bundled map stores countries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader_country: Option<String>,
+    pub closest_region: Region,
+    /// Deterministic backup for `closest_region`; absent when it is the only
+    /// available region.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secondary_region: Option<Region>,
+    /// Why the embedded map was rejected at start-up. When set, no leader is
+    /// looked up in the map and every region comes from the hash fallback.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_map_error: Option<String>,
+    /// Only with `map_provenance: true`.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub map_provenance: Option<MapProvenance>,
+    /// Set when the embedded map is older than `max_map_age_slots`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub map_stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upcoming_leaders: Option<Vec<LeaderWindow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_table: Option<EpochRouteTable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fanout_plan: Option<FanoutPlan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator_schedule: Option<ValidatorSchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_regions: Option<Vec<PreferredRegion>>,
 }
 
+/// One entry of `preferred_regions`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-pub enum ServerRegion {
-    #[serde(rename = "Dubai")]
-    Dubai,
-    #[serde(rename = "Frankfurt")]
-    Frankfurt,
-    #[serde(rename = "NewYork")]
-    NewYork,
-    #[serde(rename = "Tokyo")]
-    Tokyo,
-}
-
-impl From<Region> for ServerRegion {
-    fn from(value: Region) -> Self {
-        match value {
-            Region::Dubai => Self::Dubai,
-            Region::Frankfurt => Self::Frankfurt,
-            Region::NewYork => Self::NewYork,
-            Region::Tokyo => Self::Tokyo,
-        }
-    }
+pub struct PreferredRegion {
+    pub region: Region,
+    /// Estimate from the leader's bucket, not a sort key. `None` when the leader's geo is unknown and there is nothing to
+    /// estimate from.
+    pub estimated_rtt_ms: Option<u32>,
+    /// Great-circle distance from the leader to the region's datacenter,
+    /// only when the bundled map stores leader coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<u32>,
+}
+
+/// Where `leader_geo` came from. Anything but `Map` means the geo is
+/// `UNKNOWN` and says why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeoSource {
+    /// The map has a known bucket for the leader.
+    Map,
+    /// The leader is in the map, but its bucket is `Unknown` or a byte this
+    /// build doesn't know.
+    MapUnknown,
+    /// The leader is not in the map.
+    NotInMap,
+    /// The map is malformed or failed validation.
+    InvalidMap,
+    /// The leader pubkey is not a base58 32-byte key, so it can't be looked
+    /// up at all; only the hash fallback applies.
+    FallbackHash,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -49,76 +119,181 @@ pub struct ProcedureErrorData {
 }
 
 impl CustomProcedure for LeaderRoutingProcedure {
-    type Params = Option<JsonValue>;
+    type Params = Option<LeaderRoutingParams>;
     type SuccessData = LeaderRoutingOutput;
     type ErrorData = ProcedureErrorData;
 
-    async fn run(_params: Self::Params) -> Result<Self::SuccessData, RpcError<Self::ErrorData>> {
+    async fn run(params: Self::Params) -> Result<Self::SuccessData, RpcError<Self::ErrorData>> {
+        let params = params.unwrap_or_default();
+        params.validate().map_err(invalid_params_error)?;
+        let regions = params.available_regions();
         let rpc = RpcClient::new();
 
-        let slot = rpc.get_slot().await.map_err(|err| {
-            internal_error("get_slot", format!("failed to fetch current slot: {err}"))
-        })?;
+        let slot = match params.slot {
+            Some(slot) => slot,
+            None => rpc.get_slot().await.map_err(|err| {
+                internal_error("get_slot", format!("failed to fetch current slot: {err}"))
+            })?,
+        };
 
-        let epoch_schedule = rpc.get_epoch_schedule().await.map_err(|err| {
+        let SlotLeaders {
+            leaders: slot_leaders,
+            leader_schedule,
+        } = fetch_slot_leaders(&rpc, slot, params.slot_leaders_limit()).await?;
+        let leader = slot_leaders.first().cloned().ok_or_else(|| {
             internal_error(
-                "get_epoch_schedule",
-                format!("failed to fetch epoch schedule: {err}"),
+                "resolve_leader",
+                format!("no leader returned for slot {slot}"),
             )
         })?;
 
-        let leader_schedule = rpc.get_leader_schedule(Some(slot)).await.map_err(|err| {
-            internal_error(
-                "get_leader_schedule",
-                format!("failed to fetch leader schedule for slot {slot}: {err}"),
-            )
-        })?;
+        let (geo_map, geo_map_error) = geo_map_for_routing(embedded_geo_map());
 
-        let leader_schedule = leader_schedule.ok_or_else(|| {
-            internal_error(
-                "get_leader_schedule",
-                format!("leader schedule was missing for slot {slot}"),
-            )
-        })?;
+        let provenance = MapProvenance::for_slot(slot);
+        let max_map_age_slots = params
+            .max_map_age_slots
+            .unwrap_or(DEFAULT_MAX_MAP_AGE_SLOTS);
+        let map_stale = provenance.is_stale(max_map_age_slots);
+        if map_stale {
+            warn!(
+                "leader geo map is {} slots old (generated at slot {}), over the {max_map_age_slots} slot threshold",
+                provenance.map_age_slots, provenance.map_rpc_slot
+            );
+        }
+        let map_provenance = params.map_provenance.then_some(provenance);
 
-        let epoch = epoch_schedule.get_epoch(slot);
-        let first_slot_in_epoch = epoch_schedule.get_first_slot_in_epoch(epoch);
-        let slot_index =
-            usize::try_from(slot.saturating_sub(first_slot_in_epoch)).map_err(|err| {
-                internal_error(
-                    "slot_index",
-                    format!(
-                        "failed to compute slot index in epoch for slot {slot} and first slot {first_slot_in_epoch}: {err}"
-                    ),
-                )
-            })?;
+        let (leader_geo, geo_source, closest_region) =
+            derive_leader_geo_and_region(&leader, geo_map, &regions);
+        // A rejected map is swapped for an empty one, which reads as "not in
+        // map"; report the real reason.
+        let geo_source = if geo_map_error.is_some() {
+            GeoSource::InvalidMap
+        } else {
+            geo_source
+        };
+        let leader_country = lookup_leader_country_in_map(geo_map, &leader);
+        let preferred_regions = preferred_regions(
+            &leader_geo,
+            region_override_in_map(geo_map, &leader),
+            lookup_leader_coordinates_in_map(geo_map, &leader),
+            &leader,
+            &regions,
+        );
+        let secondary_region = secondary_region(&preferred_regions);
 
-        let leader = find_leader_for_slot_index(&leader_schedule, slot_index).ok_or_else(|| {
-            internal_error(
-                "resolve_leader",
-                format!(
-                    "no leader found in leader schedule for slot {slot} (slot_index={slot_index})"
-                ),
+        info!(
+            "slot={slot} leader={leader} leader_geo={} geo_source={geo_source:?} leader_country={} closest_region={closest_region:?} secondary_region={secondary_region:?}",
+            leader_geo,
+            leader_country.as_deref().unwrap_or("-")
+        );
+
+        let preferred_regions = params.preferred_regions.then_some(preferred_regions);
+
+        let upcoming_leaders = params.lookahead.map(|lookahead| {
+            collapse_leader_windows(
+                slot,
+                slot_leaders_within(&slot_leaders, lookahead),
+                geo_map,
+                &regions,
             )
-        })?;
+        });
 
-        let (leader_geo, closest_region) =
-            derive_leader_geo_and_region(&leader, LEADER_GEO_MAP_BIN);
+        let fanout_plan = params.fanout.and_then(|horizon| {
+            plan_region_fanout(&collapse_leader_windows(
+                slot,
+                slot_leaders_within(&slot_leaders, horizon),
+                geo_map,
+                &regions,
+            ))
+        });
 
-        info!(
-            "slot={slot} leader={leader} leader_geo={} closest_region={closest_region:?}",
-            leader_geo
-        );
+        let route_table = if params.route_table {
+            Some(
+                fetch_epoch_route_table(&rpc, slot, leader_schedule.as_ref(), geo_map, &regions)
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let validator_schedule = match &params.validator {
+            Some(validator) => Some(
+                fetch_validator_schedule(
+                    &rpc,
+                    validator,
+                    slot,
+                    leader_schedule.as_ref(),
+                    geo_map,
+                    &regions,
+                )
+                .await?,
+            ),
+            None => None,
+        };
 
         Ok(LeaderRoutingOutput {
             slot,
             leader,
             leader_geo,
+            geo_source,
+            leader_country,
             closest_region,
+            secondary_region,
+            geo_map_error,
+            map_provenance,
+            map_stale,
+            upcoming_leaders,
+            route_table,
+            fanout_plan,
+            validator_schedule,
+            preferred_regions,
         })
     }
 }
 
+/// `LEADER_GEO_MAP_BIN` once it passed a full validation (structure, sort
+/// order, bucket bytes, checksum). Validation runs once per process.
+fn embedded_geo_map() -> Result<&'static [u8], &'static LeaderGeoMapError> {
+    static VALIDATION: OnceLock<Result<(), LeaderGeoMapError>> = OnceLock::new();
+    VALIDATION
+        .get_or_init(|| validate_geo_map(LEADER_GEO_MAP_BIN))
+        .as_ref()
+        .map(|()| LEADER_GEO_MAP_BIN)
+}
+
+fn validate_geo_map(geo_map: &[u8]) -> Result<(), LeaderGeoMapError> {
+    let validation = LeaderGeoMap::parse(geo_map).and_then(|map| map.validate());
+    if let Err(err) = &validation {
+        error!("embedded leader geo map failed validation: {err}");
+    }
+    validation
+}
+
+/// The map to look leaders up in, and the error to report when it was
+/// rejected. A rejected map is replaced by an empty one rather than trusted.
+fn geo_map_for_routing<'a>(
+    validation: Result<&'a [u8], &LeaderGeoMapError>,
+) -> (&'a [u8], Option<String>) {
+    match validation {
+        Ok(geo_map) => (geo_map, None),
+        Err(err) => {
+            warn!("leader geo map is invalid ({err}); routing every leader by hash fallback");
+            (&[], Some(err.to_string()))
+        }
+    }
+}
+
+fn invalid_params_error(details: String) -> RpcError<ProcedureErrorData> {
+    RpcError {
+        code: ERROR_CODE_INVALID_PARAMS,
+        message: "invalid leader routing params".to_string(),
+        data: Some(ProcedureErrorData {
+            stage: "params",
+            details,
+        }),
+    }
+}
+
 fn internal_error(stage: &'static str, details: String) -> RpcError<ProcedureErrorData> {
     RpcError {
         code: ERROR_CODE_INTERNAL,
@@ -127,29 +302,303 @@ fn internal_error(stage: &'static str, details: String) -> RpcError<ProcedureErr
     }
 }
 
-fn find_leader_for_slot_index(
+/// The RPC calls slot leader resolution makes. Implemented by `RpcClient`;
+/// tests serve both resolution strategies from one fixture through it.
+pub(crate) trait LeaderScheduleRpc {
+    /// `getSlotLeaders`.
+    async fn slot_leaders(&self, start_slot: u64, limit: u64) -> Result<Vec<String>, String>;
+    /// The epoch containing `slot`.
+    async fn epoch_bounds(&self, slot: u64) -> Result<EpochBounds, String>;
+    /// `getLeaderSchedule` for the epoch containing `slot`.
+    async fn leader_schedule(&self, slot: u64) -> Result<Option<RpcLeaderSchedule>, String>;
+}
+
+impl LeaderScheduleRpc for RpcClient {
+    async fn slot_leaders(&self, start_slot: u64, limit: u64) -> Result<Vec<String>, String> {
+        let leaders = self
+            .get_slot_leaders(start_slot, limit)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(leaders.iter().map(ToString::to_string).collect())
+    }
+
+    async fn epoch_bounds(&self, slot: u64) -> Result<EpochBounds, String> {
+        let epoch_schedule = self
+            .get_epoch_schedule()
+            .await
+            .map_err(|err| err.to_string())?;
+        let epoch = epoch_schedule.get_epoch(slot);
+        Ok(EpochBounds {
+            epoch,
+            first_slot: epoch_schedule.get_first_slot_in_epoch(epoch),
+            last_slot: epoch_schedule.get_last_slot_in_epoch(epoch),
+        })
+    }
+
+    async fn leader_schedule(&self, slot: u64) -> Result<Option<RpcLeaderSchedule>, String> {
+        self.get_leader_schedule(Some(slot))
+            .await
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// An epoch and its inclusive slot span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EpochBounds {
+    pub(crate) epoch: u64,
+    pub(crate) first_slot: u64,
+    pub(crate) last_slot: u64,
+}
+
+impl EpochBounds {
+    pub(crate) fn contains(self, slot: u64) -> bool {
+        (self.first_slot..=self.last_slot).contains(&slot)
+    }
+}
+
+/// A leader schedule and the epoch it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EpochLeaderSchedule {
+    pub(crate) bounds: EpochBounds,
+    pub(crate) leader_schedule: RpcLeaderSchedule,
+}
+
+/// Leaders of consecutive slots, from [`fetch_slot_leaders`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SlotLeaders {
+    leaders: Vec<String>,
+    /// Schedule of the first slot's epoch, when the fallback had to download
+    /// it; later stages reuse it instead of fetching it again.
+    leader_schedule: Option<EpochLeaderSchedule>,
+}
+
+/// Resolves the leaders of `limit` consecutive slots starting at `start_slot`.
+///
+/// Asks RPC for just that window via `getSlotLeaders` and only falls back to
+/// downloading the full epoch schedule when the targeted call fails or comes
+/// back short.
+async fn fetch_slot_leaders(
+    rpc: &impl LeaderScheduleRpc,
+    start_slot: u64,
+    limit: u64,
+) -> Result<SlotLeaders, RpcError<ProcedureErrorData>> {
+    match rpc.slot_leaders(start_slot, limit).await {
+        Ok(leaders) if leaders.len() as u64 == limit => {
+            return Ok(SlotLeaders {
+                leaders,
+                leader_schedule: None,
+            });
+        }
+        Ok(leaders) => warn!(
+            "getSlotLeaders returned {} of {limit} leaders for slot {start_slot}; falling back to leader schedule",
+            leaders.len()
+        ),
+        Err(err) => warn!(
+            "getSlotLeaders failed for slot {start_slot}: {err}; falling back to leader schedule"
+        ),
+    }
+
+    fetch_slot_leaders_from_leader_schedule(rpc, start_slot, limit).await
+}
+
+/// Full-schedule fallback for [`fetch_slot_leaders`]. Windows that cross an
+/// epoch boundary pull the leader schedule of each epoch they touch.
+async fn fetch_slot_leaders_from_leader_schedule(
+    rpc: &impl LeaderScheduleRpc,
+    start_slot: u64,
+    limit: u64,
+) -> Result<SlotLeaders, RpcError<ProcedureErrorData>> {
+    let end_slot = start_slot.saturating_add(limit);
+    let mut leaders = Vec::new();
+    let mut first_leader_schedule = None;
+    let mut slot = start_slot;
+
+    while slot < end_slot {
+        let epoch_leader_schedule = fetch_epoch_leader_schedule(rpc, slot).await?;
+        let bounds = epoch_leader_schedule.bounds;
+
+        let slot_index = slot_index_in_epoch(slot, bounds.first_slot)?;
+        let count = end_slot.min(bounds.last_slot.saturating_add(1)) - slot;
+
+        let epoch_leaders = slot_leaders_from_schedule(
+            &epoch_leader_schedule.leader_schedule,
+            slot_index,
+            count,
+        )
+            .ok_or_else(|| {
+                internal_error(
+                    "resolve_leader",
+                    format!(
+                        "no leader found in leader schedule for slot {slot} (slot_index={slot_index}, limit={count})"
+                    ),
+                )
+            })?;
+
+        leaders.extend(epoch_leaders);
+        first_leader_schedule.get_or_insert(epoch_leader_schedule);
+        slot += count;
+    }
+
+    Ok(SlotLeaders {
+        leaders,
+        leader_schedule: first_leader_schedule,
+    })
+}
+
+async fn fetch_epoch_bounds(
+    rpc: &impl LeaderScheduleRpc,
+    slot: u64,
+) -> Result<EpochBounds, RpcError<ProcedureErrorData>> {
+    rpc.epoch_bounds(slot).await.map_err(|err| {
+        internal_error(
+            "get_epoch_schedule",
+            format!("failed to fetch epoch schedule: {err}"),
+        )
+    })
+}
+
+/// The leader schedule of the epoch containing `slot`, with its bounds.
+pub(crate) async fn fetch_epoch_leader_schedule(
+    rpc: &impl LeaderScheduleRpc,
+    slot: u64,
+) -> Result<EpochLeaderSchedule, RpcError<ProcedureErrorData>> {
+    let bounds = fetch_epoch_bounds(rpc, slot).await?;
+    let leader_schedule = fetch_leader_schedule(rpc, slot).await?;
+    Ok(EpochLeaderSchedule {
+        bounds,
+        leader_schedule,
+    })
+}
+
+async fn fetch_leader_schedule(
+    rpc: &impl LeaderScheduleRpc,
+    slot: u64,
+) -> Result<RpcLeaderSchedule, RpcError<ProcedureErrorData>> {
+    fetch_published_leader_schedule(rpc, slot)
+        .await?
+        .ok_or_else(|| {
+            internal_error(
+                "get_leader_schedule",
+                format!("leader schedule was missing for slot {slot}"),
+            )
+        })
+}
+
+/// The leader schedule of the epoch containing `slot`, or `None` when RPC
+/// has not published it yet.
+async fn fetch_published_leader_schedule(
+    rpc: &impl LeaderScheduleRpc,
+    slot: u64,
+) -> Result<Option<RpcLeaderSchedule>, RpcError<ProcedureErrorData>> {
+    rpc.leader_schedule(slot).await.map_err(|err| {
+        internal_error(
+            "get_leader_schedule",
+            format!("failed to fetch leader schedule for slot {slot}: {err}"),
+        )
+    })
+}
+
+fn slot_index_in_epoch(
+    slot: u64,
+    first_slot_in_epoch: u64,
+) -> Result<usize, RpcError<ProcedureErrorData>> {
+    usize::try_from(slot.saturating_sub(first_slot_in_epoch)).map_err(|err| {
+        internal_error(
+            "slot_index",
+            format!(
+                "failed to compute slot index in epoch for slot {slot} and first slot {first_slot_in_epoch}: {err}"
+            ),
+        )
+    })
+}
+
+fn slot_leaders_within(slot_leaders: &[String], horizon: u64) -> &[String] {
+    let horizon = usize::try_from(horizon).unwrap_or(usize::MAX);
+    &slot_leaders[..horizon.min(slot_leaders.len())]
+}
+
+/// Builds the same `[leader; limit]` window `getSlotLeaders` would return,
+/// but from a full epoch leader schedule.
+fn slot_leaders_from_schedule(
     leader_schedule: &RpcLeaderSchedule,
-    slot_index: usize,
-) -> Option<String> {
-    leader_schedule
-        .iter()
-        .filter_map(|(leader, slots)| slots.contains(&slot_index).then_some(leader.as_str()))
-        .min()
-        .map(ToString::to_string)
+    start_slot_index: usize,
+    limit: u64,
+) -> Option<Vec<String>> {
+    let end_slot_index = start_slot_index.checked_add(usize::try_from(limit).ok()?)?;
+    let slot_leaders = invert_leader_schedule(leader_schedule, start_slot_index..end_slot_index)?;
+    Some(slot_leaders.into_iter().map(ToString::to_string).collect())
+}
+
+fn derive_leader_geo_and_region(
+    leader_pubkey: &str,
+    geo_map: &[u8],
+    regions: &[Region],
+) -> (String, GeoSource, Region) {
+    let (leader_geo, geo_source) = match lookup_leader_geo_in_map(geo_map, leader_pubkey) {
+        Ok(leader_geo) => (leader_geo, GeoSource::Map),
+        Err(geo_source) => (UNKNOWN_GEO, geo_source),
+    };
+    let leader_geo = leader_geo.to_string();
+    let closest_region = closest_region(
+        &leader_geo,
+        region_override_in_map(geo_map, leader_pubkey),
+        lookup_leader_coordinates_in_map(geo_map, leader_pubkey),
+        leader_pubkey,
+        regions,
+    );
+    (leader_geo, geo_source, closest_region)
 }
 
-fn derive_leader_geo_and_region(leader_pubkey: &str, geo_map: &[u8]) -> (String, ServerRegion) {
-    let leader_geo = lookup_leader_geo_in_map(geo_map, leader_pubkey)
-        .unwrap_or(UNKNOWN_GEO)
-        .to_string();
-    let closest_region = choose_region(&leader_geo, leader_pubkey);
-    (leader_geo, closest_region)
+/// The rules' override for the leader's country, then the datacenter nearest
+/// its coordinates, then [`choose_region`]. Unavailable overrides are skipped.
+fn closest_region(
+    leader_geo: &str,
+    region_override: Option<Region>,
+    coordinates: Option<Coordinates>,
+    leader_pubkey: &str,
+    regions: &[Region],
+) -> Region {
+    region_override
+        .filter(|region| regions.contains(region))
+        .or_else(|| coordinates.and_then(|location| nearest_available_region(location, regions)))
+        .unwrap_or_else(|| choose_region(leader_geo, leader_pubkey, regions))
 }
 
-fn lookup_leader_geo_in_map(geo_map: &[u8], leader_pubkey: &str) -> Option<&'static str> {
+/// Region the rules force for the leader's stored country, if any.
+fn region_override_in_map(geo_map: &[u8], leader_pubkey: &str) -> Option<Region> {
+    let country = lookup_leader_country_in_map(geo_map, leader_pubkey)?;
+    region_override_for_country(&country)
+}
+
+/// The leader's geo label, or why there is none.
+///
+/// Maps that store countries are re-bucketed with the current rule table, so
+/// rule changes apply without regenerating the map. Countries the rules don't
+/// cover keep the bucket recorded at generation time.
+fn lookup_leader_geo_in_map(
+    geo_map: &[u8],
+    leader_pubkey: &str,
+) -> Result<&'static str, GeoSource> {
+    let leader_pubkey = decode_leader_pubkey(leader_pubkey).ok_or(GeoSource::FallbackHash)?;
+    let map = LeaderGeoMap::parse(geo_map).map_err(|_| GeoSource::InvalidMap)?;
+    let record = map.get_record(&leader_pubkey).ok_or(GeoSource::NotInMap)?;
+    let bucket = record
+        .country
+        .map(bucket_from_country_iso)
+        .filter(|bucket| *bucket != GeoBucket::Unknown)
+        .map_or(record.bucket, GeoBucket::as_u8);
+    geo_bucket_to_label(bucket).ok_or(GeoSource::MapUnknown)
+}
+
+fn lookup_leader_country_in_map(geo_map: &[u8], leader_pubkey: &str) -> Option<String> {
+    let leader_pubkey = decode_leader_pubkey(leader_pubkey)?;
+    let record = lookup_geo_record(geo_map, &leader_pubkey)?;
+    record.country.map(ToString::to_string)
+}
+
+fn lookup_leader_coordinates_in_map(geo_map: &[u8], leader_pubkey: &str) -> Option<Coordinates> {
     let leader_pubkey = decode_leader_pubkey(leader_pubkey)?;
-    let bucket = lookup_geo_bucket(geo_map, &leader_pubkey)?;
-    geo_bucket_to_label(bucket)
+    lookup_geo_record(geo_map, &leader_pubkey)?.coordinates
 }
 
 fn decode_leader_pubkey(leader_pubkey: &str) -> Option<[u8; 32]> {
@@ -162,27 +611,11 @@ fn decode_leader_pubkey(leader_pubkey: &str) -> Option<[u8; 32]> {
     Some(bytes)
 }
 
-fn lookup_geo_bucket(geo_map: &[u8], leader_pubkey: &[u8; 32]) -> Option<u8> {
-    if !geo_map.len().is_multiple_of(LEADER_GEO_RECORD_SIZE) {
-        return None;
-    }
-
-    let mut left = 0usize;
-    let mut right = geo_map.len() / LEADER_GEO_RECORD_SIZE;
-
-    while left < right {
-        let mid = left + (right - left) / 2;
-        let offset = mid * LEADER_GEO_RECORD_SIZE;
-        let key = &geo_map[offset..offset + 32];
-
-        match key.cmp(leader_pubkey) {
-            std::cmp::Ordering::Less => left = mid + 1,
-            std::cmp::Ordering::Greater => right = mid,
-            std::cmp::Ordering::Equal => return geo_map.get(offset + 32).copied(),
-        }
-    }
-
-    None
+fn lookup_geo_record<'a>(
+    geo_map: &'a [u8],
+    leader_pubkey: &[u8; 32],
+) -> Option<LeaderGeoRecord<'a>> {
+    LeaderGeoMap::parse(geo_map).ok()?.get_record(leader_pubkey)
 }
 
 fn geo_bucket_to_label(bucket: u8) -> Option<&'static str> {
@@ -190,30 +623,75 @@ fn geo_bucket_to_label(bucket: u8) -> Option<&'static str> {
     (bucket != GeoBucket::Unknown).then_some(bucket.label())
 }
 
-fn choose_region(leader_geo: &str, leader_pubkey: &str) -> ServerRegion {
-    region_from_geo(leader_geo).unwrap_or_else(|| fallback_region(leader_pubkey))
+/// Picks the region for a leader out of `regions`, the regions currently
+/// available for routing (catalog order, never empty).
+fn choose_region(leader_geo: &str, leader_pubkey: &str, regions: &[Region]) -> Region {
+    region_from_geo(leader_geo, regions).unwrap_or_else(|| fallback_region(leader_pubkey, regions))
 }
 
-fn region_from_geo(leader_geo: &str) -> Option<ServerRegion> {
-    region_from_geo_input(leader_geo).map(Into::into)
+/// The available region whose datacenter is nearest to `location`.
+fn nearest_available_region(location: Coordinates, regions: &[Region]) -> Option<Region> {
+    regions_ranked_by_distance(location)
+        .into_iter()
+        .map(|(region, _)| region)
+        .find(|region| regions.contains(region))
 }
 
-fn fallback_region(leader_pubkey: &str) -> ServerRegion {
-    match fnv1a64(leader_pubkey.as_bytes()) % 4 {
-        0 => ServerRegion::Dubai,
-        1 => ServerRegion::Frankfurt,
-        2 => ServerRegion::NewYork,
-        _ => ServerRegion::Tokyo,
+/// Available regions in routing preference order, headed by the region
+/// [`closest_region`] picks for the same inputs: the override, then nearest
+/// first when the leader's coordinates are known, otherwise the bucket's
+/// preference list followed by the rendezvous weight order.
+///
+/// The order is not sorted by `estimated_rtt_ms` or `distance_km`; those are
+/// reported per region for information only.
+fn preferred_regions(
+    leader_geo: &str,
+    region_override: Option<Region>,
+    coordinates: Option<Coordinates>,
+    leader_pubkey: &str,
+    regions: &[Region],
+) -> Vec<PreferredRegion> {
+    let bucket = bucket_from_geo_input(leader_geo);
+    let order: Vec<Region> = match coordinates {
+        Some(location) => regions_ranked_by_distance(location)
+            .into_iter()
+            .map(|(region, _)| region)
+            .collect(),
+        None => select_regions(bucket, regions)
+            .chain(regions_by_rendezvous_weight(leader_pubkey, regions))
+            .collect(),
+    };
+
+    let mut preferred: Vec<PreferredRegion> = Vec::with_capacity(regions.len());
+    for region in region_override.into_iter().chain(order) {
+        if !regions.contains(&region) || preferred.iter().any(|known| known.region == region) {
+            continue;
+        }
+        preferred.push(PreferredRegion {
+            region,
+            estimated_rtt_ms: estimated_rtt_ms(bucket, region),
+            distance_km: coordinates
+                .map(|location| location.distance_km(region_coordinates(region)).round() as u32),
+        });
     }
+    preferred
 }
 
-fn fnv1a64(bytes: &[u8]) -> u64 {
-    let mut hash: u64 = 0xcbf29ce484222325;
-    for byte in bytes {
-        hash ^= u64::from(*byte);
-        hash = hash.wrapping_mul(0x100000001b3);
+/// The rule-table region for `leader_geo`, or the next available one in its
+/// bucket's preference list when that region is excluded.
+fn region_from_geo(leader_geo: &str, regions: &[Region]) -> Option<Region> {
+    let primary = region_from_geo_input(leader_geo)?;
+    if regions.contains(&primary) {
+        return Some(primary);
     }
-    hash
+
+    select_regions(bucket_from_geo_input(leader_geo), regions).next()
+}
+
+/// Where to fail over to when `closest_region` is unreachable: the runner-up
+/// in `preferred_regions`. `None` when no other region is available.
+fn secondary_region(preferred_regions: &[PreferredRegion]) -> Option<Region> {
+    preferred_regions.get(1).map(|preferred| preferred.region)
 }
 
 zela_std::zela_custom_procedure!(LeaderRoutingProcedure);
@@ -221,47 +699,423 @@ zela_std::zela_custom_procedure!(LeaderRoutingProcedure);
 #[cfg(test)]
 mod tests {
     use super::*;
+    use leader_geo_map::{DEFAULT_KEY_PREFIX_SIZE, LeaderGeoMapBuilder};
     use serde::Deserialize;
+    use std::cell::Cell;
     use std::collections::HashMap;
+    use std::task::{Context, Poll, Waker};
+
+    const LEADER_GEO_MAP_META_JSON: &str = include_str!("../data/leader_geo_map.meta.json");
 
     #[test]
     fn region_mapping_works() {
-        assert_eq!(region_from_geo("EU"), Some(ServerRegion::Frankfurt));
-        assert_eq!(region_from_geo("ae"), Some(ServerRegion::Dubai));
-        assert_eq!(region_from_geo("us"), Some(ServerRegion::NewYork));
-        assert_eq!(region_from_geo("JP"), Some(ServerRegion::Tokyo));
-        assert_eq!(region_from_geo("unknown"), None);
+        assert_eq!(region_from_geo("EU", &ALL_REGIONS), Some(Region::Frankfurt));
+        assert_eq!(region_from_geo("ae", &ALL_REGIONS), Some(Region::Dubai));
+        assert_eq!(region_from_geo("us", &ALL_REGIONS), Some(Region::NewYork));
+        assert_eq!(region_from_geo("JP", &ALL_REGIONS), Some(Region::Tokyo));
+        assert_eq!(region_from_geo("unknown", &ALL_REGIONS), None);
     }
 
     #[test]
     fn fallback_region_is_deterministic() {
         let leader = "SomeLeaderPubkey111111111111111111111111111";
-        let first = fallback_region(leader);
-        let second = fallback_region(leader);
+        let first = fallback_region(leader, &ALL_REGIONS);
+        let second = fallback_region(leader, &ALL_REGIONS);
         assert_eq!(first, second);
     }
 
     #[test]
-    fn find_leader_for_slot_is_stable() {
+    fn slot_leaders_from_schedule_breaks_ties_by_pubkey() {
         let mut schedule: HashMap<String, Vec<usize>> = HashMap::new();
         schedule.insert("validator-z".to_string(), vec![8]);
         schedule.insert("validator-a".to_string(), vec![8]);
 
-        let leader = find_leader_for_slot_index(&schedule, 8).unwrap();
-        assert_eq!(leader, "validator-a");
+        assert_eq!(
+            slot_leaders_from_schedule(&schedule, 8, 1),
+            Some(vec!["validator-a".to_string()])
+        );
+    }
+
+    fn fixture_leader_schedule() -> RpcLeaderSchedule {
+        let mut schedule: HashMap<String, Vec<usize>> = HashMap::new();
+        schedule.insert("validator-a".to_string(), vec![0, 1, 2, 3, 12, 13, 14, 15]);
+        schedule.insert("validator-b".to_string(), vec![4, 5, 6, 7]);
+        schedule.insert("validator-c".to_string(), vec![8, 9, 10, 11]);
+        schedule
+    }
+
+    /// Serves `getSlotLeaders` and `getLeaderSchedule` from one list of
+    /// per-slot leaders, split into epochs of `SLOTS_PER_EPOCH` slots. Epochs
+    /// past the end of the list have no published schedule yet.
+    pub(crate) struct FixtureRpc {
+        slot_leaders: Vec<&'static str>,
+        get_slot_leaders: FixtureSlotLeaders,
+        pub(crate) leader_schedule_calls: Cell<usize>,
+    }
+
+    #[derive(Clone, Copy)]
+    pub(crate) enum FixtureSlotLeaders {
+        Full,
+        Short,
+        Failing,
+    }
+
+    impl FixtureRpc {
+        pub(crate) const SLOTS_PER_EPOCH: u64 = 16;
+
+        pub(crate) fn new(
+            slot_leaders: Vec<&'static str>,
+            get_slot_leaders: FixtureSlotLeaders,
+        ) -> Self {
+            Self {
+                slot_leaders,
+                get_slot_leaders,
+                leader_schedule_calls: Cell::new(0),
+            }
+        }
+    }
+
+    impl LeaderScheduleRpc for FixtureRpc {
+        async fn slot_leaders(&self, start_slot: u64, limit: u64) -> Result<Vec<String>, String> {
+            let limit = match self.get_slot_leaders {
+                FixtureSlotLeaders::Full => limit,
+                FixtureSlotLeaders::Short => limit - 1,
+                FixtureSlotLeaders::Failing => return Err("connection reset".to_string()),
+            };
+            Ok(self.slot_leaders[start_slot as usize..][..limit as usize]
+                .iter()
+                .map(ToString::to_string)
+                .collect())
+        }
+
+        async fn epoch_bounds(&self, slot: u64) -> Result<EpochBounds, String> {
+            let epoch = slot / Self::SLOTS_PER_EPOCH;
+            let first_slot = epoch * Self::SLOTS_PER_EPOCH;
+            Ok(EpochBounds {
+                epoch,
+                first_slot,
+                last_slot: first_slot + Self::SLOTS_PER_EPOCH - 1,
+            })
+        }
+
+        async fn leader_schedule(&self, slot: u64) -> Result<Option<RpcLeaderSchedule>, String> {
+            self.leader_schedule_calls
+                .set(self.leader_schedule_calls.get() + 1);
+            let EpochBounds {
+                first_slot,
+                last_slot,
+                ..
+            } = self.epoch_bounds(slot).await?;
+            if last_slot as usize >= self.slot_leaders.len() {
+                return Ok(None);
+            }
+            let mut schedule = RpcLeaderSchedule::new();
+            for slot in first_slot..=last_slot {
+                schedule
+                    .entry(self.slot_leaders[slot as usize].to_string())
+                    .or_default()
+                    .push((slot - first_slot) as usize);
+            }
+            Ok(Some(schedule))
+        }
+    }
+
+    /// Polls a fixture future to completion; fixture calls never wait.
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("fixture future is pending"),
+        }
+    }
+
+    #[test]
+    fn slot_leaders_from_schedule_matches_get_slot_leaders_window() {
+        let slot_leaders = [
+            ["validator-a"; 4],
+            ["validator-b"; 4],
+            ["validator-c"; 4],
+            ["validator-a"; 4],
+            ["validator-c"; 4],
+            ["validator-d"; 4],
+            ["validator-b"; 4],
+            ["validator-a"; 4],
+        ]
+        .concat();
+
+        // Within the first epoch, and across the boundary into the second.
+        for (start_slot, limit) in [(2, 8), (10, 12), (16, 16)] {
+            let fetched = [
+                FixtureSlotLeaders::Full,
+                FixtureSlotLeaders::Short,
+                FixtureSlotLeaders::Failing,
+            ]
+            .map(|get_slot_leaders| {
+                let rpc = FixtureRpc::new(slot_leaders.clone(), get_slot_leaders);
+                block_on(fetch_slot_leaders(&rpc, start_slot, limit))
+                    .unwrap()
+                    .leaders
+            });
+
+            let expected = &slot_leaders[start_slot as usize..][..limit as usize];
+            for leaders in fetched {
+                assert_eq!(leaders, expected, "start_slot={start_slot} limit={limit}");
+            }
+        }
+    }
+
+    #[test]
+    fn fetch_slot_leaders_keeps_the_downloaded_leader_schedule() {
+        let rpc = FixtureRpc::new(vec!["validator-a"; 32], FixtureSlotLeaders::Full);
+        let fetched = block_on(fetch_slot_leaders(&rpc, 12, 8)).unwrap();
+        assert_eq!(fetched.leader_schedule, None);
+        assert_eq!(rpc.leader_schedule_calls.get(), 0);
+
+        // The window spans two epochs; the routed slot's epoch is kept.
+        let rpc = FixtureRpc::new(vec!["validator-a"; 32], FixtureSlotLeaders::Short);
+        let fetched = block_on(fetch_slot_leaders(&rpc, 12, 8)).unwrap();
+        assert_eq!(
+            fetched.leader_schedule.unwrap().bounds,
+            EpochBounds {
+                epoch: 0,
+                first_slot: 0,
+                last_slot: 15,
+            }
+        );
+        assert_eq!(rpc.leader_schedule_calls.get(), 2);
+    }
+
+    #[test]
+    fn fetch_slot_leaders_reports_missing_leader_schedule() {
+        let rpc = FixtureRpc::new(vec!["validator-a"; 16], FixtureSlotLeaders::Failing);
+
+        let err = block_on(fetch_slot_leaders(&rpc, 12, 8)).unwrap_err();
+        assert_eq!(err.data.unwrap().stage, "get_leader_schedule");
+    }
+
+    #[test]
+    fn slot_leaders_from_schedule_rejects_window_past_schedule_end() {
+        let schedule = fixture_leader_schedule();
+        assert_eq!(
+            slot_leaders_from_schedule(&schedule, 15, 1),
+            Some(vec!["validator-a".to_string()])
+        );
+        assert_eq!(slot_leaders_from_schedule(&schedule, 15, 2), None);
     }
 
     #[test]
     fn choose_region_uses_geo_then_fallback() {
-        assert_eq!(choose_region("EU", "x"), ServerRegion::Frankfurt);
+        assert_eq!(choose_region("EU", "x", &ALL_REGIONS), Region::Frankfurt);
 
-        let from_unknown_geo = choose_region(UNKNOWN_GEO, "validator-x");
-        let deterministic_again = choose_region(UNKNOWN_GEO, "validator-x");
+        let from_unknown_geo = choose_region(UNKNOWN_GEO, "validator-x", &ALL_REGIONS);
+        let deterministic_again = choose_region(UNKNOWN_GEO, "validator-x", &ALL_REGIONS);
         assert_eq!(from_unknown_geo, deterministic_again);
     }
 
     #[test]
-    fn lookup_geo_bucket_uses_binary_search() {
+    fn choose_region_skips_excluded_regions() {
+        let without_tokyo = [Region::Dubai, Region::Frankfurt, Region::NewYork];
+
+        assert_eq!(choose_region("APAC", "x", &without_tokyo), Region::Dubai);
+        assert_eq!(choose_region("EU", "x", &without_tokyo), Region::Frankfurt);
+        assert_ne!(
+            choose_region(UNKNOWN_GEO, "validator-x", &without_tokyo),
+            Region::Tokyo
+        );
+    }
+
+    fn secondary_for(
+        leader_geo: &str,
+        region_override: Option<Region>,
+        coordinates: Option<Coordinates>,
+        regions: &[Region],
+    ) -> Option<Region> {
+        secondary_region(&preferred_regions(
+            leader_geo,
+            region_override,
+            coordinates,
+            "x",
+            regions,
+        ))
+    }
+
+    #[test]
+    fn secondary_region_follows_bucket_preferences() {
+        let without_dubai = [Region::Frankfurt, Region::NewYork, Region::Tokyo];
+
+        assert_eq!(
+            secondary_for("ME", None, None, &ALL_REGIONS),
+            Some(Region::Frankfurt)
+        );
+        assert_eq!(choose_region("ME", "x", &without_dubai), Region::Frankfurt);
+        assert_eq!(
+            secondary_for("ME", None, None, &without_dubai),
+            Some(Region::Tokyo)
+        );
+
+        let preferred = preferred_regions(UNKNOWN_GEO, None, None, "validator-x", &ALL_REGIONS);
+        assert_eq!(
+            secondary_region(&preferred),
+            Some(regions_by_rendezvous_weight("validator-x", &ALL_REGIONS)[1])
+        );
+        assert_ne!(
+            secondary_region(&preferred),
+            Some(choose_region(UNKNOWN_GEO, "validator-x", &ALL_REGIONS))
+        );
+
+        assert_eq!(secondary_for("EU", None, None, &[Region::Frankfurt]), None);
+    }
+
+    #[test]
+    fn secondary_region_follows_coordinates_and_overrides() {
+        let warsaw = Coordinates::new(52.23, 21.01);
+        let istanbul = Coordinates::new(41.01, 28.98);
+
+        for (leader_geo, region_override, coordinates, closest, secondary) in [
+            ("EU", None, warsaw, Region::Frankfurt, Region::Dubai),
+            (
+                "ME",
+                Some(Region::Tokyo),
+                istanbul,
+                Region::Tokyo,
+                Region::Frankfurt,
+            ),
+        ] {
+            let preferred = preferred_regions(
+                leader_geo,
+                region_override,
+                Some(coordinates),
+                "x",
+                &ALL_REGIONS,
+            );
+            assert_eq!(
+                closest_region(
+                    leader_geo,
+                    region_override,
+                    Some(coordinates),
+                    "x",
+                    &ALL_REGIONS
+                ),
+                closest
+            );
+            assert_eq!(preferred[0].region, closest);
+            assert_eq!(secondary_region(&preferred), Some(preferred[1].region));
+            assert_eq!(secondary_region(&preferred), Some(secondary));
+        }
+    }
+
+    #[test]
+    fn preferred_regions_start_with_chosen_region() {
+        for leader_geo in [
+            "EU",
+            "NA",
+            "APAC",
+            "ME",
+            "LATAM",
+            "AFRICA",
+            "OCEANIA",
+            UNKNOWN_GEO,
+        ] {
+            let ranked = preferred_regions(leader_geo, None, None, "validator-x", &ALL_REGIONS);
+
+            assert_eq!(ranked.len(), ALL_REGIONS.len());
+            assert_eq!(
+                ranked[0].region,
+                choose_region(leader_geo, "validator-x", &ALL_REGIONS)
+            );
+            assert_eq!(
+                ranked[0].estimated_rtt_ms.is_some(),
+                leader_geo != UNKNOWN_GEO
+            );
+        }
+    }
+
+    #[test]
+    fn coordinates_route_to_nearest_available_region() {
+        let istanbul_leader = "7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q";
+        let istanbul = Coordinates::new(41.01, 28.98);
+        let mut builder = LeaderGeoMapBuilder::new();
+        builder
+            .insert_with_location(
+                decode_leader_pubkey(istanbul_leader).unwrap(),
+                GeoBucket::Me,
+                Some("TR"),
+                Some(istanbul),
+            )
+            .unwrap();
+        let geo_map = builder.build().unwrap();
+
+        // The ME bucket alone would route to Dubai.
+        assert_eq!(
+            derive_leader_geo_and_region(istanbul_leader, &geo_map, &ALL_REGIONS),
+            ("ME".to_string(), GeoSource::Map, Region::Frankfurt)
+        );
+
+        let without_frankfurt = [Region::Dubai, Region::NewYork, Region::Tokyo];
+        let (_, _, closest_region) =
+            derive_leader_geo_and_region(istanbul_leader, &geo_map, &without_frankfurt);
+        assert_eq!(closest_region, Region::Dubai);
+
+        let ranked = preferred_regions("ME", None, Some(istanbul), istanbul_leader, &ALL_REGIONS);
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|ranked| ranked.region)
+                .collect::<Vec<_>>(),
+            [
+                Region::Frankfurt,
+                Region::Dubai,
+                Region::NewYork,
+                Region::Tokyo
+            ]
+        );
+        assert!(
+            ranked
+                .windows(2)
+                .all(|pair| pair[0].distance_km <= pair[1].distance_km)
+        );
+        assert_eq!(ranked[0].estimated_rtt_ms, Some(110));
+    }
+
+    #[test]
+    fn preferred_regions_put_region_override_first() {
+        // Stands in for a `region_overrides` entry for the leader's country;
+        // the embedded rules have none.
+        let region_override = Some(Region::Tokyo);
+        let istanbul = Coordinates::new(41.01, 28.98);
+        let without_tokyo = [Region::Dubai, Region::Frankfurt, Region::NewYork];
+
+        for coordinates in [None, Some(istanbul)] {
+            for regions in [&ALL_REGIONS[..], &without_tokyo] {
+                let closest_region =
+                    closest_region("ME", region_override, coordinates, "validator-x", regions);
+                let ranked =
+                    preferred_regions("ME", region_override, coordinates, "validator-x", regions);
+
+                assert_eq!(ranked[0].region, closest_region);
+                assert_eq!(ranked.len(), regions.len());
+            }
+        }
+
+        let ranked = preferred_regions("ME", region_override, None, "validator-x", &ALL_REGIONS);
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|ranked| ranked.region)
+                .collect::<Vec<_>>(),
+            [
+                Region::Tokyo,
+                Region::Dubai,
+                Region::Frankfurt,
+                Region::NewYork
+            ]
+        );
+        assert_eq!(ranked[0].estimated_rtt_ms, Some(150));
+    }
+
+    #[test]
+    fn lookup_geo_record_uses_binary_search() {
         let geo_map = build_geo_map(&[
             ("7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q", 1),
             ("2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR", 2),
@@ -269,10 +1123,13 @@ mod tests {
         ]);
 
         let key = decode_leader_pubkey("2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR").unwrap();
-        assert_eq!(lookup_geo_bucket(&geo_map, &key), Some(2));
+        assert_eq!(
+            lookup_geo_record(&geo_map, &key).map(|record| record.bucket),
+            Some(2)
+        );
 
         let missing_key = decode_leader_pubkey("11111111111111111111111111111111").unwrap();
-        assert_eq!(lookup_geo_bucket(&geo_map, &missing_key), None);
+        assert_eq!(lookup_geo_record(&geo_map, &missing_key), None);
     }
 
     #[test]
@@ -286,23 +1143,226 @@ mod tests {
 
         assert_eq!(
             lookup_leader_geo_in_map(&geo_map, "9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv"),
-            Some("APAC")
+            Ok("APAC")
         );
         assert_eq!(
             lookup_leader_geo_in_map(&geo_map, "11111111111111111111111111111111"),
-            None
+            Err(GeoSource::NotInMap)
+        );
+    }
+
+    #[test]
+    fn lookup_leader_geo_in_map_rebuckets_stored_country() {
+        let mut builder = LeaderGeoMapBuilder::new();
+        for (pubkey, bucket, country) in [
+            // Stored bucket is stale; the current rules put DE in EU.
+            (
+                "7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q",
+                GeoBucket::Na,
+                Some("DE"),
+            ),
+            // Generated before BR had a rule; the current rules put it in LATAM.
+            (
+                "2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR",
+                GeoBucket::Unknown,
+                Some("BR"),
+            ),
+            // No rule assigns AQ, so the stored bucket stays.
+            (
+                "9YvS2fH5A2m2W6B8hWcP8d9Yhrb2nJbLg2xwqQ8CbW2s",
+                GeoBucket::Me,
+                Some("AQ"),
+            ),
+            (
+                "9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv",
+                GeoBucket::Apac,
+                None,
+            ),
+        ] {
+            builder
+                .insert_with_country(decode_leader_pubkey(pubkey).unwrap(), bucket, country)
+                .unwrap();
+        }
+        let geo_map = builder.build().unwrap();
+
+        let lookup = |pubkey| {
+            (
+                lookup_leader_geo_in_map(&geo_map, pubkey),
+                lookup_leader_country_in_map(&geo_map, pubkey),
+            )
+        };
+        assert_eq!(
+            lookup("7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q"),
+            (Ok("EU"), Some("DE".to_string()))
+        );
+        assert_eq!(
+            lookup("2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR"),
+            (Ok("LATAM"), Some("BR".to_string()))
+        );
+        assert_eq!(
+            lookup("9YvS2fH5A2m2W6B8hWcP8d9Yhrb2nJbLg2xwqQ8CbW2s"),
+            (Ok("ME"), Some("AQ".to_string()))
         );
+        assert_eq!(
+            lookup("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv"),
+            (Ok("APAC"), None)
+        );
+    }
+
+    #[test]
+    fn lookup_leader_geo_in_map_reports_why_geo_is_unknown() {
+        let geo_map = build_geo_map(&[
+            (
+                "7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q",
+                GeoBucket::Unknown.as_u8(),
+            ),
+            ("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv", 3),
+        ]);
+
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv"),
+            Ok("APAC")
+        );
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q"),
+            Err(GeoSource::MapUnknown)
+        );
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR"),
+            Err(GeoSource::NotInMap)
+        );
+        assert_eq!(
+            lookup_leader_geo_in_map(&[1, 2, 3], "9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv"),
+            Err(GeoSource::InvalidMap)
+        );
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "not-a-pubkey"),
+            Err(GeoSource::FallbackHash)
+        );
+
+        let (leader_geo, geo_source, _) =
+            derive_leader_geo_and_region("not-a-pubkey", &geo_map, &ALL_REGIONS);
+        assert_eq!(
+            (leader_geo.as_str(), geo_source),
+            (UNKNOWN_GEO, GeoSource::FallbackHash)
+        );
+        assert_eq!(
+            serde_json::to_value(GeoSource::NotInMap).unwrap(),
+            serde_json::json!("not_in_map")
+        );
+    }
+
+    #[test]
+    fn lookup_geo_record_rejects_misaligned_data() {
+        assert_eq!(lookup_geo_record(&[1, 2, 3], &[0u8; 32]), None);
+    }
+
+    #[test]
+    fn lookup_leader_geo_in_map_reads_compact_maps() {
+        let geo_map = geo_map_builder(&[
+            ("7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q", 1),
+            ("2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR", 2),
+            ("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv", 3),
+        ])
+        .build_compact(DEFAULT_KEY_PREFIX_SIZE)
+        .unwrap();
+
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "2jXy799ynN5A6xM4mT2QPY2ATqNnSboP8Gr3HdWu3UwR"),
+            Ok("NA")
+        );
+        assert_eq!(
+            lookup_leader_geo_in_map(&geo_map, "11111111111111111111111111111111"),
+            Err(GeoSource::NotInMap)
+        );
+    }
+
+    #[test]
+    fn lookup_geo_record_reads_v1_maps() {
+        let entries = [
+            ("7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q", 1),
+            ("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv", 3),
+        ];
+        let geo_map = geo_map_builder(&entries).build_v1();
+
+        for (pubkey, bucket) in entries {
+            let key = decode_leader_pubkey(pubkey).unwrap();
+            assert_eq!(
+                lookup_geo_record(&geo_map, &key).map(|record| record.bucket),
+                Some(bucket)
+            );
+        }
     }
 
     #[test]
-    fn lookup_geo_bucket_rejects_misaligned_data() {
-        assert_eq!(lookup_geo_bucket(&[1, 2, 3], &[0u8; 32]), None);
+    fn embedded_geo_map_is_valid() {
+        let geo_map = LeaderGeoMap::parse(LEADER_GEO_MAP_BIN).unwrap();
+        assert!(!geo_map.is_empty());
+        assert_eq!(geo_map.validate(), Ok(()));
+        assert_eq!(embedded_geo_map(), Ok(LEADER_GEO_MAP_BIN));
+    }
+
+    #[test]
+    fn map_metadata_matches_embedded_map() {
+        let geo_map = LeaderGeoMap::parse(LEADER_GEO_MAP_BIN).unwrap();
+        assert_eq!(map_metadata::SCHEMA_VERSION, geo_map.version());
+        assert_eq!(map_metadata::TOTAL_LEADERS, geo_map.len());
+
+        let meta: serde_json::Value = serde_json::from_str(LEADER_GEO_MAP_META_JSON).unwrap();
+        assert_eq!(meta["rpc_slot"], map_metadata::RPC_SLOT);
+        assert_eq!(
+            meta["generated_at_unix_secs"],
+            map_metadata::GENERATED_AT_UNIX_SECS
+        );
+        assert_eq!(meta["mapped_leaders"], map_metadata::MAPPED_LEADERS);
+        assert_eq!(meta["map_sha256"], map_metadata::MAP_SHA256);
+        for (bucket, count) in map_metadata::BUCKET_COUNTS {
+            assert_eq!(meta["bucket_counts"][bucket.label()], count);
+        }
+        assert_eq!(
+            map_metadata::BUCKET_COUNTS.len(),
+            meta["bucket_counts"].as_object().unwrap().len()
+        );
+
+        let map = LeaderGeoMap::parse(LEADER_GEO_MAP_BIN).unwrap();
+        for (bucket, count) in map_metadata::BUCKET_COUNTS {
+            let in_map = map
+                .iter()
+                .filter(|(_, code)| *code == bucket.as_u8())
+                .count();
+            assert_eq!(in_map as u64, count, "{bucket:?}");
+        }
+    }
+
+    #[test]
+    fn corrupt_geo_map_is_reported_and_not_used() {
+        let mut corrupt = build_geo_map(&[
+            ("7XSXtg2CWwjWCa7j4kXfYLMi8xawJbq6XW6xMa6Y5P9Q", 1),
+            ("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv", 3),
+        ]);
+        let last_bucket = corrupt.len() - 9;
+        corrupt[last_bucket] = GeoBucket::Eu.as_u8();
+
+        let validation = validate_geo_map(&corrupt);
+        assert!(matches!(
+            validation,
+            Err(LeaderGeoMapError::ChecksumMismatch { .. })
+        ));
+
+        let (geo_map, geo_map_error) =
+            geo_map_for_routing(validation.as_ref().map(|()| corrupt.as_slice()));
+        assert!(geo_map.is_empty());
+        assert!(geo_map_error.unwrap().starts_with("checksum mismatch"));
+
+        let (geo_map, geo_map_error) = geo_map_for_routing(Ok(&corrupt));
+        assert_eq!(geo_map, corrupt.as_slice());
+        assert_eq!(geo_map_error, None);
     }
 
     #[derive(Debug, Deserialize)]
     struct RpcEnvelope {
         #[serde(default)]
-        params: Option<JsonValue>,
+        params: Option<LeaderRoutingParams>,
     }
 
     #[test]
@@ -337,7 +1397,25 @@ mod tests {
             "params":{}
         }"#;
         let envelope: RpcEnvelope = serde_json::from_str(request).unwrap();
-        assert_eq!(envelope.params, Some(serde_json::json!({})));
+        assert_eq!(envelope.params, Some(LeaderRoutingParams::default()));
+    }
+
+    #[test]
+    fn slot_params_shape_is_supported() {
+        let request = r#"{
+            "jsonrpc":"2.0",
+            "id":1,
+            "method":"zela.geolocation#hash",
+            "params":{"slot":400403429}
+        }"#;
+        let envelope: RpcEnvelope = serde_json::from_str(request).unwrap();
+        assert_eq!(
+            envelope.params,
+            Some(LeaderRoutingParams {
+                slot: Some(400403429),
+                ..LeaderRoutingParams::default()
+            })
+        );
     }
 
     #[test]
@@ -345,24 +1423,27 @@ mod tests {
         let leader = "9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv";
         let malformed_geo_map = [1u8, 2, 3];
 
-        let (leader_geo, closest_region) = derive_leader_geo_and_region(leader, &malformed_geo_map);
+        let (leader_geo, geo_source, closest_region) =
+            derive_leader_geo_and_region(leader, &malformed_geo_map, &ALL_REGIONS);
 
         assert_eq!(leader_geo, UNKNOWN_GEO);
-        assert_eq!(closest_region, fallback_region(leader));
+        assert_eq!(geo_source, GeoSource::InvalidMap);
+        assert_eq!(closest_region, fallback_region(leader, &ALL_REGIONS));
     }
 
     fn build_geo_map(entries: &[(&str, u8)]) -> Vec<u8> {
-        let mut decoded: Vec<([u8; 32], u8)> = entries
+        geo_map_builder(entries).build().unwrap()
+    }
+
+    fn geo_map_builder(entries: &[(&str, u8)]) -> LeaderGeoMapBuilder {
+        entries
             .iter()
-            .map(|(pubkey, bucket)| (decode_leader_pubkey(pubkey).unwrap(), *bucket))
-            .collect();
-        decoded.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
-
-        let mut bytes = Vec::with_capacity(decoded.len() * LEADER_GEO_RECORD_SIZE);
-        for (pubkey, bucket) in decoded {
-            bytes.extend_from_slice(&pubkey);
-            bytes.push(bucket);
-        }
-        bytes
+            .map(|(pubkey, bucket)| {
+                (
+                    decode_leader_pubkey(pubkey).unwrap(),
+                    GeoBucket::from_u8(*bucket).unwrap(),
+                )
+            })
+            .collect()
     }
 }
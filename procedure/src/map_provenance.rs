@@ -0,0 +1,67 @@
+use serde::Serialize;
+
+use crate::map_metadata;
+
+/// Default `max_map_age_slots`: about 30 days of 400 ms slots.
+pub const DEFAULT_MAX_MAP_AGE_SLOTS: u64 = 6_480_000;
+
+/// Where the embedded map comes from and how old it is, from the metadata
+/// sidecar checked in next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MapProvenance {
+    /// Unix time the map was generated at.
+    pub map_generated_at: u64,
+    /// Slot RPC reported when the map was generated.
+    pub map_rpc_slot: u64,
+    pub map_sha256: &'static str,
+    /// Slots between `map_rpc_slot` and the routed slot; zero when routing a
+    /// slot older than the map.
+    pub map_age_slots: u64,
+}
+
+impl MapProvenance {
+    pub(crate) fn for_slot(slot: u64) -> Self {
+        Self {
+            map_generated_at: map_metadata::GENERATED_AT_UNIX_SECS,
+            map_rpc_slot: map_metadata::RPC_SLOT,
+            map_sha256: map_metadata::MAP_SHA256,
+            map_age_slots: map_age_slots(map_metadata::RPC_SLOT, slot),
+        }
+    }
+
+    pub(crate) fn is_stale(&self, max_map_age_slots: u64) -> bool {
+        self.map_age_slots > max_map_age_slots
+    }
+}
+
+fn map_age_slots(map_rpc_slot: u64, slot: u64) -> u64 {
+    slot.saturating_sub(map_rpc_slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_age_is_measured_from_the_generation_slot() {
+        assert_eq!(map_age_slots(1_000, 1_250), 250);
+        assert_eq!(map_age_slots(1_000, 900), 0);
+
+        let provenance = MapProvenance::for_slot(map_metadata::RPC_SLOT + 10);
+        assert_eq!(provenance.map_age_slots, 10);
+        assert_eq!(provenance.map_rpc_slot, map_metadata::RPC_SLOT);
+        assert!(provenance.is_stale(9));
+        assert!(!provenance.is_stale(10));
+    }
+
+    #[test]
+    fn provenance_serializes_to_map_fields() {
+        let value = serde_json::to_value(MapProvenance::for_slot(0)).unwrap();
+        assert_eq!(value["map_age_slots"], 0);
+        assert_eq!(value["map_sha256"], map_metadata::MAP_SHA256);
+        assert_eq!(
+            value["map_generated_at"],
+            map_metadata::GENERATED_AT_UNIX_SECS
+        );
+    }
+}
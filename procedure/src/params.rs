@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{ALL_REGIONS, Region, decode_leader_pubkey};
+
+/// `getSlotLeaders` serves at most 5000 slots per call.
+pub const MAX_LOOKAHEAD_SLOTS: u64 = 5000;
+
+/// Typed procedure params.
+///
+/// Every field is optional so the no-input shapes (omitted, `null`, `{}`)
+/// keep resolving the leader of the current slot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeaderRoutingParams {
+    /// Route for this slot instead of the current one. Any slot RPC still
+    /// has a leader schedule for works, including slots in the next epoch.
+    #[serde(default)]
+    pub slot: Option<u64>,
+    /// Also return the leader windows of the next `lookahead` slots,
+    /// starting at the routed slot.
+    #[serde(default)]
+    pub lookahead: Option<u64>,
+    /// Also return the run-length encoded route table of the routed slot's
+    /// whole epoch.
+    #[serde(default)]
+    pub route_table: bool,
+    /// Also return the regions to submit to so that every leader of the next
+    /// `fanout` slots is reached, starting at the routed slot.
+    #[serde(default)]
+    pub fanout: Option<u64>,
+    /// Also return when this validator identity next leads, through the end
+    /// of the next epoch.
+    #[serde(default)]
+    pub validator: Option<String>,
+    /// Also return every available region in routing preference order for
+    /// the routed slot's leader.
+    #[serde(default)]
+    pub preferred_regions: bool,
+    /// Regions to keep out of routing, e.g. while under maintenance.
+    #[serde(default)]
+    pub exclude_regions: Vec<Region>,
+    /// Per-region health; regions marked `false` are excluded as well.
+    /// Regions missing from the map count as healthy.
+    #[serde(default)]
+    pub region_health: HashMap<Region, bool>,
+    /// Also return where the embedded geo map comes from and how old it is.
+    #[serde(default)]
+    pub map_provenance: bool,
+    /// Age in slots past which the embedded map is flagged as stale
+    /// (`map_stale`). Defaults to about 30 days.
+    #[serde(default)]
+    pub max_map_age_slots: Option<u64>,
+}
+
+impl LeaderRoutingParams {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_horizon("lookahead", self.lookahead)?;
+        validate_horizon("fanout", self.fanout)?;
+        if let Some(validator) = &self.validator
+            && decode_leader_pubkey(validator).is_none()
+        {
+            return Err(format!(
+                "validator must be a base58 encoded 32-byte pubkey, got {validator:?}"
+            ));
+        }
+        if self.max_map_age_slots == Some(0) {
+            return Err("max_map_age_slots must be at least 1".to_string());
+        }
+        if self.available_regions().is_empty() {
+            return Err(
+                "exclude_regions and region_health leave no region to route to".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Regions routing may pick from, in catalog order.
+    pub fn available_regions(&self) -> Vec<Region> {
+        ALL_REGIONS
+            .into_iter()
+            .filter(|region| !self.exclude_regions.contains(region))
+            .filter(|region| self.region_health.get(region).copied().unwrap_or(true))
+            .collect()
+    }
+
+    /// Number of slot leaders to fetch starting at the routed slot, enough
+    /// for the routed slot itself and every horizon that was asked for.
+    pub fn slot_leaders_limit(&self) -> u64 {
+        [self.lookahead, self.fanout]
+            .into_iter()
+            .flatten()
+            .fold(1, u64::max)
+    }
+}
+
+fn validate_horizon(name: &str, horizon: Option<u64>) -> Result<(), String> {
+    match horizon {
+        Some(slots) if !(1..=MAX_LOOKAHEAD_SLOTS).contains(&slots) => Err(format!(
+            "{name} must be between 1 and {MAX_LOOKAHEAD_SLOTS} slots, got {slots}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_param_is_parsed() {
+        let params: LeaderRoutingParams =
+            serde_json::from_value(serde_json::json!({ "slot": 400403429 })).unwrap();
+        assert_eq!(params.slot, Some(400403429));
+    }
+
+    #[test]
+    fn null_slot_param_means_current_slot() {
+        let params: LeaderRoutingParams =
+            serde_json::from_value(serde_json::json!({ "slot": null })).unwrap();
+        assert_eq!(params, LeaderRoutingParams::default());
+    }
+
+    #[test]
+    fn route_table_param_is_parsed() {
+        let params: LeaderRoutingParams =
+            serde_json::from_value(serde_json::json!({ "route_table": true })).unwrap();
+        assert!(params.route_table);
+        assert!(!LeaderRoutingParams::default().route_table);
+    }
+
+    #[test]
+    fn lookahead_is_bounded() {
+        let params = |lookahead| LeaderRoutingParams {
+            lookahead: Some(lookahead),
+            ..LeaderRoutingParams::default()
+        };
+
+        assert!(params(16).validate().is_ok());
+        assert!(params(MAX_LOOKAHEAD_SLOTS).validate().is_ok());
+        assert!(params(0).validate().is_err());
+        assert!(params(MAX_LOOKAHEAD_SLOTS + 1).validate().is_err());
+    }
+
+    #[test]
+    fn slot_leaders_limit_covers_every_horizon() {
+        assert_eq!(LeaderRoutingParams::default().slot_leaders_limit(), 1);
+
+        let params = LeaderRoutingParams {
+            lookahead: Some(16),
+            fanout: Some(64),
+            ..LeaderRoutingParams::default()
+        };
+        assert_eq!(params.slot_leaders_limit(), 64);
+    }
+
+    #[test]
+    fn validator_must_be_a_pubkey() {
+        let params = |validator: &str| LeaderRoutingParams {
+            validator: Some(validator.to_string()),
+            ..LeaderRoutingParams::default()
+        };
+
+        assert!(
+            params("9QxCLckBiJc783jnMvXZubK4wH86Eqqvashtrwvcsgkv")
+                .validate()
+                .is_ok()
+        );
+        assert!(params("not-a-pubkey").validate().is_err());
+    }
+
+    #[test]
+    fn available_regions_drop_excluded_and_unhealthy_regions() {
+        let params: LeaderRoutingParams = serde_json::from_value(serde_json::json!({
+            "exclude_regions": ["Tokyo"],
+            "region_health": { "Dubai": false, "Frankfurt": true }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            params.available_regions(),
+            vec![Region::Frankfurt, Region::NewYork]
+        );
+        assert_eq!(
+            LeaderRoutingParams::default().available_regions(),
+            ALL_REGIONS.to_vec()
+        );
+    }
+
+    #[test]
+    fn excluding_every_region_is_rejected() {
+        let params: LeaderRoutingParams = serde_json::from_value(serde_json::json!({
+            "exclude_regions": ["Dubai", "Frankfurt", "NewYork", "Tokyo"]
+        }))
+        .unwrap();
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn map_age_threshold_must_be_positive() {
+        let params: LeaderRoutingParams = serde_json::from_value(serde_json::json!({
+            "map_provenance": true,
+            "max_map_age_slots": 0
+        }))
+        .unwrap();
+        assert!(params.map_provenance);
+        assert!(params.validate().is_err());
+
+        let params = LeaderRoutingParams {
+            max_map_age_slots: Some(1),
+            ..LeaderRoutingParams::default()
+        };
+        assert_eq!(params.validate(), Ok(()));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result =
+            serde_json::from_value::<LeaderRoutingParams>(serde_json::json!({ "slott": 1 }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn negative_slot_is_rejected() {
+        let result =
+            serde_json::from_value::<LeaderRoutingParams>(serde_json::json!({ "slot": -1 }));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use zela_std::RpcError;
+use zela_std::rpc_client::response::RpcLeaderSchedule;
+
+use crate::leader_windows::{LeaderWindow, collapse_leader_windows};
+use crate::{
+    EpochLeaderSchedule, LeaderScheduleRpc, ProcedureErrorData, Region,
+    fetch_epoch_leader_schedule, internal_error, slot_index_in_epoch,
+};
+
+/// The whole epoch's slot -> region timeline, one segment per leader window.
+///
+/// Segments are ordered, contiguous and cover every slot from
+/// `first_slot` through `valid_until_slot`, so clients can cache the table
+/// and route locally until the epoch ends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EpochRouteTable {
+    pub epoch: u64,
+    pub first_slot: u64,
+    pub valid_until_slot: u64,
+    pub segments: Vec<LeaderWindow>,
+}
+
+/// Builds the route table for the epoch containing `slot`, reusing
+/// `leader_schedule` when it is already that epoch's schedule.
+pub(crate) async fn fetch_epoch_route_table(
+    rpc: &impl LeaderScheduleRpc,
+    slot: u64,
+    leader_schedule: Option<&EpochLeaderSchedule>,
+    geo_map: &[u8],
+    regions: &[Region],
+) -> Result<EpochRouteTable, RpcError<ProcedureErrorData>> {
+    let fetched;
+    let EpochLeaderSchedule {
+        bounds,
+        leader_schedule,
+    } = match leader_schedule {
+        Some(leader_schedule) if leader_schedule.bounds.contains(slot) => leader_schedule,
+        _ => {
+            fetched = fetch_epoch_leader_schedule(rpc, slot).await?;
+            &fetched
+        }
+    };
+    let slot_count = slot_index_in_epoch(bounds.last_slot, bounds.first_slot)? + 1;
+
+    build_epoch_route_table(
+        bounds.epoch,
+        bounds.first_slot,
+        slot_count,
+        leader_schedule,
+        geo_map,
+        regions,
+    )
+    .ok_or_else(|| {
+        internal_error(
+            "route_table",
+            format!(
+                "leader schedule for epoch {} does not cover all {slot_count} slots starting at {}",
+                bounds.epoch, bounds.first_slot
+            ),
+        )
+    })
+}
+
+fn build_epoch_route_table(
+    epoch: u64,
+    first_slot: u64,
+    slot_count: usize,
+    leader_schedule: &RpcLeaderSchedule,
+    geo_map: &[u8],
+    regions: &[Region],
+) -> Option<EpochRouteTable> {
+    let slot_leaders = invert_leader_schedule(leader_schedule, 0..slot_count)?;
+    let segments = collapse_leader_windows(first_slot, &slot_leaders, geo_map, regions);
+
+    Some(EpochRouteTable {
+        epoch,
+        first_slot,
+        valid_until_slot: segments.last()?.end_slot,
+        segments,
+    })
+}
+
+/// Turns `leader -> [slot_index]` into `slot_index -> leader` for the slot
+/// indexes in `window`, in one pass over the schedule.
+///
+/// Ties resolve to the smallest pubkey. Returns `None` if any slot in
+/// `window` has no leader.
+pub(crate) fn invert_leader_schedule(
+    leader_schedule: &RpcLeaderSchedule,
+    window: Range<usize>,
+) -> Option<Vec<&str>> {
+    let mut slot_leaders: Vec<Option<&str>> = vec![None; window.len()];
+
+    for (leader, slot_indexes) in leader_schedule {
+        for slot_index in slot_indexes {
+            if let Some(offset) = slot_index.checked_sub(window.start)
+                && let Some(entry) = slot_leaders.get_mut(offset)
+                && entry.is_none_or(|current| leader.as_str() < current)
+            {
+                *entry = Some(leader.as_str());
+            }
+        }
+    }
+
+    slot_leaders.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{FixtureRpc, FixtureSlotLeaders, block_on};
+    use crate::{ALL_REGIONS, EpochBounds};
+    use std::collections::HashMap;
+
+    fn fixture_leader_schedule() -> RpcLeaderSchedule {
+        let mut schedule: HashMap<String, Vec<usize>> = HashMap::new();
+        schedule.insert("validator-a".to_string(), vec![0, 1, 2, 3, 8, 9, 10, 11]);
+        schedule.insert("validator-b".to_string(), vec![4, 5, 6, 7, 12, 13, 14, 15]);
+        schedule.insert("validator-z".to_string(), vec![12]);
+        schedule
+    }
+
+    #[test]
+    fn invert_leader_schedule_maps_every_slot_to_its_leader() {
+        let schedule = fixture_leader_schedule();
+
+        let slot_leaders = invert_leader_schedule(&schedule, 0..16).unwrap();
+
+        // validator-z also claims slot 12 and loses the tie to validator-b.
+        let expected = [["validator-a"; 4], ["validator-b"; 4]].concat().repeat(2);
+        assert_eq!(slot_leaders, expected);
+    }
+
+    #[test]
+    fn invert_leader_schedule_requires_every_slot() {
+        assert_eq!(
+            invert_leader_schedule(&fixture_leader_schedule(), 0..17),
+            None
+        );
+        assert_eq!(
+            invert_leader_schedule(&fixture_leader_schedule(), 10..14),
+            Some(vec![
+                "validator-a",
+                "validator-a",
+                "validator-b",
+                "validator-b"
+            ])
+        );
+    }
+
+    #[test]
+    fn epoch_route_table_is_contiguous_and_run_length_encoded() {
+        let table =
+            build_epoch_route_table(7, 1_000, 16, &fixture_leader_schedule(), &[], &ALL_REGIONS)
+                .unwrap();
+
+        let spans: Vec<(u64, u64, &str)> = table
+            .segments
+            .iter()
+            .map(|segment| {
+                (
+                    segment.start_slot,
+                    segment.end_slot,
+                    segment.leader.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (1_000, 1_003, "validator-a"),
+                (1_004, 1_007, "validator-b"),
+                (1_008, 1_011, "validator-a"),
+                (1_012, 1_015, "validator-b"),
+            ]
+        );
+        assert_eq!(table.first_slot, 1_000);
+        assert_eq!(table.valid_until_slot, 1_015);
+    }
+
+    #[test]
+    fn fetch_epoch_route_table_reuses_the_fetched_leader_schedule() {
+        let slot_leaders = [["validator-a"; 4], ["validator-b"; 4]].concat().repeat(2);
+        let rpc = FixtureRpc::new(slot_leaders, FixtureSlotLeaders::Full);
+
+        let fetched = block_on(fetch_epoch_route_table(&rpc, 5, None, &[], &ALL_REGIONS)).unwrap();
+        assert_eq!(rpc.leader_schedule_calls.get(), 1);
+        assert_eq!((fetched.epoch, fetched.first_slot), (0, 0));
+        assert_eq!(fetched.valid_until_slot, FixtureRpc::SLOTS_PER_EPOCH - 1);
+
+        let leader_schedule = EpochLeaderSchedule {
+            bounds: EpochBounds {
+                epoch: 0,
+                first_slot: 0,
+                last_slot: FixtureRpc::SLOTS_PER_EPOCH - 1,
+            },
+            leader_schedule: block_on(crate::fetch_leader_schedule(&rpc, 5)).unwrap(),
+        };
+        rpc.leader_schedule_calls.set(0);
+        let reused = block_on(fetch_epoch_route_table(
+            &rpc,
+            5,
+            Some(&leader_schedule),
+            &[],
+            &ALL_REGIONS,
+        ))
+        .unwrap();
+        assert_eq!(rpc.leader_schedule_calls.get(), 0);
+        assert_eq!(reused, fetched);
+    }
+
+    #[test]
+    fn fetch_epoch_route_table_reports_missing_leader_schedule() {
+        let rpc = FixtureRpc::new(vec!["validator-a"; 16], FixtureSlotLeaders::Full);
+
+        let err = block_on(fetch_epoch_route_table(&rpc, 20, None, &[], &ALL_REGIONS)).unwrap_err();
+        assert_eq!(err.data.unwrap().stage, "get_leader_schedule");
+    }
+}
@@ -0,0 +1,181 @@
+use serde::Serialize;
+use zela_std::RpcError;
+
+use crate::fanout::SlotRange;
+use crate::{
+    EpochLeaderSchedule, LeaderScheduleRpc, ProcedureErrorData, Region,
+    derive_leader_geo_and_region, fetch_epoch_leader_schedule, fetch_published_leader_schedule,
+};
+
+/// When and where a given validator leads next.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidatorSchedule {
+    pub validator: String,
+    pub leader_geo: String,
+    pub closest_region: Region,
+    /// Leader slots from the routed slot through the end of the next epoch,
+    /// merged into inclusive ranges (normally 4-slot leader windows).
+    pub upcoming_slots: Vec<SlotRange>,
+}
+
+/// `leader_schedule`, when given, is reused for its epoch. The next epoch's
+/// schedule may not be published yet; its slots are then left out.
+pub(crate) async fn fetch_validator_schedule(
+    rpc: &impl LeaderScheduleRpc,
+    validator: &str,
+    from_slot: u64,
+    leader_schedule: Option<&EpochLeaderSchedule>,
+    geo_map: &[u8],
+    regions: &[Region],
+) -> Result<ValidatorSchedule, RpcError<ProcedureErrorData>> {
+    let fetched;
+    let current = match leader_schedule {
+        Some(leader_schedule) if leader_schedule.bounds.contains(from_slot) => leader_schedule,
+        _ => {
+            fetched = fetch_epoch_leader_schedule(rpc, from_slot).await?;
+            &fetched
+        }
+    };
+    let next_epoch_first_slot = current.bounds.last_slot + 1;
+    let next = fetch_published_leader_schedule(rpc, next_epoch_first_slot).await?;
+
+    let mut upcoming_slots = Vec::new();
+    for (leader_schedule, first_slot_in_epoch) in [
+        (Some(&current.leader_schedule), current.bounds.first_slot),
+        (next.as_ref(), next_epoch_first_slot),
+    ] {
+        if let Some(slot_indexes) = leader_schedule.and_then(|schedule| schedule.get(validator)) {
+            upcoming_slots.extend(upcoming_leader_ranges(
+                slot_indexes,
+                first_slot_in_epoch,
+                from_slot,
+            ));
+        }
+    }
+
+    let (leader_geo, _, closest_region) = derive_leader_geo_and_region(validator, geo_map, regions);
+
+    Ok(ValidatorSchedule {
+        validator: validator.to_string(),
+        leader_geo,
+        closest_region,
+        upcoming_slots,
+    })
+}
+
+/// Converts a validator's epoch slot indexes into absolute slot ranges at or
+/// after `from_slot`, merging consecutive slots.
+fn upcoming_leader_ranges(
+    slot_indexes: &[usize],
+    first_slot_in_epoch: u64,
+    from_slot: u64,
+) -> Vec<SlotRange> {
+    let mut slots: Vec<u64> = slot_indexes
+        .iter()
+        .map(|slot_index| first_slot_in_epoch + *slot_index as u64)
+        .filter(|slot| *slot >= from_slot)
+        .collect();
+    slots.sort_unstable();
+    slots.dedup();
+
+    let mut ranges: Vec<SlotRange> = Vec::new();
+    for slot in slots {
+        match ranges.last_mut() {
+            Some(last) if last.end_slot + 1 == slot => last.end_slot = slot,
+            _ => ranges.push(SlotRange {
+                start_slot: slot,
+                end_slot: slot,
+            }),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ALL_REGIONS;
+    use crate::tests::{FixtureRpc, FixtureSlotLeaders, block_on};
+
+    fn range(start_slot: u64, end_slot: u64) -> SlotRange {
+        SlotRange {
+            start_slot,
+            end_slot,
+        }
+    }
+
+    #[test]
+    fn upcoming_leader_ranges_merges_leader_windows() {
+        let slot_indexes = [8, 9, 10, 11, 0, 1, 2, 3, 40, 41, 42, 43];
+
+        assert_eq!(
+            upcoming_leader_ranges(&slot_indexes, 1_000, 1_000),
+            vec![
+                range(1_000, 1_003),
+                range(1_008, 1_011),
+                range(1_040, 1_043)
+            ]
+        );
+    }
+
+    #[test]
+    fn upcoming_leader_ranges_skips_past_slots() {
+        let slot_indexes = [0, 1, 2, 3, 8, 9, 10, 11];
+
+        assert_eq!(
+            upcoming_leader_ranges(&slot_indexes, 1_000, 1_002),
+            vec![range(1_002, 1_003), range(1_008, 1_011)]
+        );
+        assert!(upcoming_leader_ranges(&slot_indexes, 1_000, 1_012).is_empty());
+    }
+
+    #[test]
+    fn validator_schedule_skips_unpublished_next_epoch() {
+        let epoch = [
+            ["validator-a"; 4],
+            ["validator-b"; 4],
+            ["validator-b"; 4],
+            ["validator-a"; 4],
+        ]
+        .concat();
+        let fetch = |slot_leaders: Vec<&'static str>| {
+            let rpc = FixtureRpc::new(slot_leaders, FixtureSlotLeaders::Full);
+            let schedule = block_on(fetch_validator_schedule(
+                &rpc,
+                "validator-a",
+                10,
+                None,
+                &[],
+                &ALL_REGIONS,
+            ))
+            .unwrap();
+            (schedule.upcoming_slots, rpc.leader_schedule_calls.get())
+        };
+
+        assert_eq!(fetch(epoch.clone()), (vec![range(12, 15)], 2));
+        assert_eq!(
+            fetch(epoch.repeat(2)),
+            (vec![range(12, 15), range(16, 19), range(28, 31)], 2)
+        );
+    }
+
+    #[test]
+    fn validator_schedule_reuses_the_fetched_leader_schedule() {
+        let rpc = FixtureRpc::new(vec!["validator-a"; 16], FixtureSlotLeaders::Full);
+        let leader_schedule = block_on(crate::fetch_epoch_leader_schedule(&rpc, 3)).unwrap();
+        rpc.leader_schedule_calls.set(0);
+
+        let schedule = block_on(fetch_validator_schedule(
+            &rpc,
+            "validator-a",
+            3,
+            Some(&leader_schedule),
+            &[],
+            &ALL_REGIONS,
+        ))
+        .unwrap();
+        assert_eq!(schedule.upcoming_slots, vec![range(3, 15)]);
+        // Only the next epoch's schedule is requested.
+        assert_eq!(rpc.leader_schedule_calls.get(), 1);
+    }
+}
@@ -23,5 +23,6 @@ echo "output_path=${OUTPUT_PATH}"
   cargo run -p geo-mapper -- \
     --rpc-url "${RPC_URL}" \
     --db "${DB_PATH}" \
-    --output "${OUTPUT_PATH}"
+    --output "${OUTPUT_PATH}" \
+    --compact
 )